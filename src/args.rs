@@ -0,0 +1,28 @@
+// Sets each value whose flag is on the command line as e.g. -gravity=1.5.
+// Values can't be negative. Returns whether any were set.
+pub fn parse_f32_flags(args: &[String], flags: &mut [(&str, &mut f32)]) -> bool {
+    let mut any_set = false;
+
+    for arg in args {
+        let mut split = arg.splitn(2, '=');
+        let (name, value) = match (split.next(), split.next()) {
+            (Some(name), Some(value)) => (name, value),
+            _ => continue,
+        };
+
+        let setting = match flags.iter_mut().find(|(flag, _)| *flag == name) {
+            Some((_, setting)) => setting,
+            None => continue,
+        };
+
+        match value.parse::<f32>() {
+            Ok(value) => {
+                **setting = value.max(0.);
+                any_set = true;
+            }
+            Err(_) => eprintln!("Ignoring {}, {} is not a number", name, value),
+        }
+    }
+
+    any_set
+}
@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, VertexAttributeValues};
+
+use crate::noise::ValueNoise;
+
+// How far the surface is pushed in and out, relative to the radius
+pub const ASTEROID_SURFACE_ROUGHNESS: f32 = 0.35;
+// Size of the lumps, in features per unit radius
+pub const ASTEROID_SURFACE_FREQUENCY: f32 = 1.2;
+pub const ASTEROID_SURFACE_OCTAVES: u32 = 4;
+pub const ASTEROID_MESH_SUBDIVISIONS: usize = 4;
+
+fn mesh_positions(mesh: &Mesh) -> Vec<[f32; 3]> {
+    match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float3(positions)) => positions.clone(),
+        _ => Vec::new(),
+    }
+}
+
+fn mesh_indices(mesh: &Mesh) -> Vec<usize> {
+    match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.iter().map(|i| *i as usize).collect(),
+        Some(Indices::U32(indices)) => indices.iter().map(|i| *i as usize).collect(),
+        None => Vec::new(),
+    }
+}
+
+// Smooth normals, from the area weighted normals of the triangles around each vertex
+fn compute_normals(positions: &[[f32; 3]], indices: &[usize]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::zero(); positions.len()];
+
+    for triangle in indices.chunks(3) {
+        if triangle.len() < 3 {
+            continue;
+        }
+        let a = Vec3::from(positions[triangle[0]]);
+        let b = Vec3::from(positions[triangle[1]]);
+        let c = Vec3::from(positions[triangle[2]]);
+        let face_normal = (b - a).cross(c - a);
+        for i in triangle {
+            normals[*i] += face_normal;
+        }
+    }
+
+    normals
+        .iter()
+        .map(|normal| normal.normalize().into())
+        .collect()
+}
+
+// Generates a lumpy asteroid from a displaced icosphere.
+// The surface is scaled so its mean radius is the radius asked for, which keeps the
+// mass of fragments and merged asteroids the same as what they were made from.
+pub fn generate_asteroid_mesh(radius: f32, seed: u64) -> Mesh {
+    let noise = ValueNoise::new(seed);
+
+    let mut mesh = Mesh::from(shape::Icosphere {
+        radius: 1.,
+        subdivisions: ASTEROID_MESH_SUBDIVISIONS,
+    });
+
+    let indices = mesh_indices(&mesh);
+
+    let surface: Vec<Vec3> = mesh_positions(&mesh)
+        .iter()
+        .map(|position| {
+            let direction = Vec3::from(*position).normalize();
+            let displacement = noise.fractal(
+                direction * ASTEROID_SURFACE_FREQUENCY,
+                ASTEROID_SURFACE_OCTAVES,
+            );
+            direction * (1. + ASTEROID_SURFACE_ROUGHNESS * displacement)
+        })
+        .collect();
+
+    let mean_radius = if surface.is_empty() {
+        1.
+    } else {
+        surface
+            .iter()
+            .map(|position| position.length())
+            .sum::<f32>()
+            / surface.len() as f32
+    };
+    let positions: Vec<[f32; 3]> = surface
+        .iter()
+        .map(|position| (*position * (radius / mean_radius)).into())
+        .collect();
+
+    let normals = compute_normals(&positions, &indices);
+
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+
+    mesh
+}
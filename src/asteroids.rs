@@ -1,82 +1,663 @@
+use std::f32::consts::PI;
+
 use bevy::prelude::*;
 use rand::Rng;
 
+use crate::asteroid_mesh::generate_asteroid_mesh;
 use crate::collision::*;
+use crate::explosion::ExplosionEvent;
 use crate::gravity::*;
+use crate::orbits::*;
+use crate::particles::{ParticleKind, Particles};
 use crate::velocity::*;
+use crate::GameState;
+
+// Asteroids have as much health as mass, scaled by this
+pub const ASTEROID_HEALTH_PER_MASS: f32 = 10.;
+
+// Dust from breaking asteroids is scaled by their radius over this
+pub const ASTEROID_DUST_RADIUS: f32 = 2.;
+
+// Fragments smaller than this vaporize instead
+pub const ASTEROID_MIN_FRAGMENT_RADIUS: f32 = 0.6;
+pub const ASTEROID_MIN_FRAGMENT_COUNT: usize = 2;
+// Up to the most fragments fragment_directions can spread out
+pub const ASTEROID_MAX_FRAGMENT_COUNT: usize = 4;
+// Speed the fragments fly apart at, relative to the asteroid they came from
+pub const ASTEROID_FRAGMENT_SEPARATION_SPEED: f32 = 3.;
+
+pub struct Asteroid {
+    pub health: f32,
+}
+
+impl Asteroid {
+    pub fn new(mass: f32) -> Asteroid {
+        Asteroid {
+            health: mass * ASTEROID_HEALTH_PER_MASS,
+        }
+    }
+}
+
+pub struct AsteroidDamageEvent {
+    pub entity: Entity,
+    pub damage: f32,
+}
+
+// Two asteroids have hit each other hard enough to merge, fragment or crater
+pub struct AsteroidImpactEvent {
+    pub asteroid_a: Entity,
+    pub asteroid_b: Entity,
+    pub energy: f32,
+}
+
+// Impacts with less energy than this only bounce
+pub const ASTEROID_IMPACT_MIN_ENERGY: f32 = 20.;
+// Asteroids closer in size than this ratio both fragment rather than the bigger one cratering
+pub const ASTEROID_FRAGMENT_MASS_RATIO: f32 = 0.5;
+// Share of the impact energy the bigger asteroid takes as damage when it is cratered
+pub const ASTEROID_CRATER_DAMAGE_SCALE: f32 = 0.5;
+// Asteroids that would merge into something bigger than this bounce instead
+pub const ASTEROID_MAX_MERGED_RADIUS: f32 = 6.;
+
+// Radians per second for an asteroid of unit radius, bigger ones tumble slower
+pub const ASTEROID_MAX_SPIN_RATE: f32 = 1.5;
+
+pub fn asteroid_mass(radius: f32) -> f32 {
+    radius * radius
+}
+
+pub fn spawn_asteroid(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    position: Vec3,
+    velocity: Vec3,
+    radius: f32,
+) {
+    let mut rng = rand::thread_rng();
+
+    let mesh = generate_asteroid_mesh(radius, rng.gen());
+    let mass = asteroid_mass(radius);
+
+    let shade = rng.gen_range(0.35, 0.6);
+    let spin_axis = Vec3::new(
+        rng.gen_range(-1.0, 1.0),
+        rng.gen_range(-1.0, 1.0),
+        rng.gen_range(-1.0, 1.0),
+    )
+    .normalize();
+    let spin_rate = rng.gen_range(0.0, ASTEROID_MAX_SPIN_RATE) / radius.max(1.);
+
+    commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(mesh),
+            material: materials.add(Color::rgb(shade, shade * 0.95, shade * 0.9).into()),
+            transform: Transform::from_translation(position),
+            ..Default::default()
+        })
+        .with(Asteroid::new(mass))
+        .with(Gravity { mass })
+        .with(Collision {
+            mass,
+            radius,
+            etype: EntityType::Asteroid,
+        })
+        .with(AngularVelocity {
+            angular_velocity: spin_axis * spin_rate,
+        })
+        .with(Velocity { velocity });
+}
+
+// How asteroids are spread around the body they orbit
+#[derive(Clone, Copy, Debug)]
+pub enum AsteroidField {
+    // Loosely bound orbits scattered between two distances
+    Belt {
+        inner_radius: f32,
+        outer_radius: f32,
+        max_eccentricity: f32,
+        max_inclination: f32,
+    },
+    // Circular orbits in a thin, flat band
+    Ring {
+        radius: f32,
+        width: f32,
+        inclination: f32,
+    },
+    // A clump of asteroids sharing nearly the same orbit
+    Cluster {
+        orbit: KeplerOrbit,
+        spread: f32,
+    },
+}
+
+// Orbits that come closer to the central body than this are pushed out
+pub const ASTEROID_MIN_PERIAPSIS: f32 = 15.;
+
+// Asteroids in a field start at least this far apart, so they don't overlap and get thrown
+// out by the collision spring. Any that can't find room in this many tries are left out.
+pub const ASTEROID_FIELD_MIN_GAP: f32 = 1.;
+pub const ASTEROID_FIELD_PLACEMENT_TRIES: usize = 50;
+
+fn random_angle(rng: &mut impl Rng) -> f32 {
+    rng.gen_range(0.0, 2. * PI)
+}
+
+impl AsteroidField {
+    fn random_orbit(&self, rng: &mut impl Rng) -> KeplerOrbit {
+        let mut orbit = match *self {
+            AsteroidField::Belt {
+                inner_radius,
+                outer_radius,
+                max_eccentricity,
+                max_inclination,
+            } => KeplerOrbit {
+                semi_major_axis: rng.gen_range(inner_radius, outer_radius),
+                eccentricity: rng.gen_range(0.0, max_eccentricity),
+                inclination: rng.gen_range(-max_inclination, max_inclination),
+                ascending_node: random_angle(rng),
+                argument_of_periapsis: random_angle(rng),
+                true_anomaly: random_angle(rng),
+            },
+            AsteroidField::Ring {
+                radius,
+                width,
+                inclination,
+            } => KeplerOrbit::circular(
+                radius + rng.gen_range(-width / 2., width / 2.),
+                inclination,
+                0.,
+                random_angle(rng),
+            ),
+            AsteroidField::Cluster { orbit, spread } => {
+                // Small changes to the orbit keep the cluster together for many periods.
+                // The period only depends on the semi-major axis, so that changes least, and
+                // the eccentricity and inclination spread the cluster out and up instead.
+                let relative_spread = spread / orbit.semi_major_axis.max(spread);
+                KeplerOrbit {
+                    semi_major_axis: orbit.semi_major_axis
+                        * (1. + rng.gen_range(-0.1, 0.1) * relative_spread),
+                    eccentricity: (orbit.eccentricity + rng.gen_range(-1.0, 1.0) * relative_spread)
+                        .max(0.),
+                    inclination: orbit.inclination + rng.gen_range(-1.0, 1.0) * relative_spread,
+                    true_anomaly: orbit.true_anomaly + rng.gen_range(-1.0, 1.0) * relative_spread,
+                    ..orbit
+                }
+            }
+        };
+
+        if orbit.periapsis() < ASTEROID_MIN_PERIAPSIS {
+            orbit.semi_major_axis = ASTEROID_MIN_PERIAPSIS / (1. - orbit.eccentricity);
+        }
+        orbit
+    }
+}
+
+// Spawns asteroids on Keplerian orbits around a body, so the field stays stable
+pub fn add_asteroid_field(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    gravitational_constant: f32,
+    central_body: &CentralBody,
+    field: &AsteroidField,
+    count: usize,
+    min_radius: f32,
+    max_radius: f32,
+) {
+    let mut rng = rand::thread_rng();
+
+    // Where each asteroid in the field starts, and its radius
+    let mut placed: Vec<(Vec3, f32)> = Vec::new();
+
+    for _ in 0..count {
+        let radius = rng.gen_range(min_radius, max_radius);
+
+        // Orbits are drawn again until one starts clear of the rest of the field
+        let is_clear = |position: Vec3| {
+            placed.iter().all(|(other_position, other_radius)| {
+                (position - *other_position).length()
+                    > radius + other_radius + ASTEROID_FIELD_MIN_GAP
+            })
+        };
+        let state = (0..ASTEROID_FIELD_PLACEMENT_TRIES)
+            .map(|_| {
+                field
+                    .random_orbit(&mut rng)
+                    .state_around(gravitational_constant, central_body)
+            })
+            .find(|(position, _)| is_clear(*position));
+        let (position, velocity) = match state {
+            Some(state) => state,
+            None => continue,
+        };
+
+        placed.push((position, radius));
+        spawn_asteroid(commands, meshes, materials, position, velocity, radius);
+    }
+}
 
 pub fn add_asteroids(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
+    gravitational_constant: f32,
+    central_body: &CentralBody,
+) {
+    add_asteroid_field(
+        commands,
+        meshes,
+        materials,
+        gravitational_constant,
+        central_body,
+        &AsteroidField::Belt {
+            inner_radius: 30.,
+            outer_radius: 75.,
+            max_eccentricity: 0.15,
+            max_inclination: 0.3,
+        },
+        100,
+        0.9,
+        3.,
+    );
+
+    add_asteroid_field(
+        commands,
+        meshes,
+        materials,
+        gravitational_constant,
+        central_body,
+        &AsteroidField::Ring {
+            radius: 18.,
+            width: 3.,
+            inclination: 0.1,
+        },
+        20,
+        0.9,
+        1.5,
+    );
+
+    add_asteroid_field(
+        commands,
+        meshes,
+        materials,
+        gravitational_constant,
+        central_body,
+        &AsteroidField::Cluster {
+            orbit: KeplerOrbit {
+                semi_major_axis: 110.,
+                eccentricity: 0.2,
+                inclination: 0.2,
+                // Away from the ascending node, where the inclination can spread the cluster up
+                argument_of_periapsis: 1.5,
+                ..Default::default()
+            },
+            spread: 8.,
+        },
+        10,
+        1.,
+        2.5,
+    );
+}
+
+// Evenly spread directions for each number of fragments, so no two are less than a right
+// angle apart
+fn fragment_directions(fragment_count: usize) -> Vec<Vec3> {
+    let directions = match fragment_count {
+        2 => vec![Vec3::unit_x(), -Vec3::unit_x()],
+        3 => vec![
+            Vec3::unit_x(),
+            Vec3::new(-0.5, 0., 0.75f32.sqrt()),
+            Vec3::new(-0.5, 0., -(0.75f32.sqrt())),
+        ],
+        _ => vec![
+            Vec3::new(1., 1., 1.),
+            Vec3::new(1., -1., -1.),
+            Vec3::new(-1., 1., -1.),
+            Vec3::new(-1., -1., 1.),
+        ],
+    };
+    directions
+        .into_iter()
+        .map(|direction| direction.normalize())
+        .collect()
+}
+
+struct Fragment {
+    offset: Vec3,
+    separation_velocity: Vec3,
+    radius: f32,
+}
+
+// Lays the fragments out just outside the asteroid they came from, so they don't overlap each
+// other and get thrown apart by the collision spring
+fn fragments(radius: f32, mass_fractions: &[f32], orientation: Quat) -> Vec<Fragment> {
+    let directions: Vec<Vec3> = fragment_directions(mass_fractions.len())
+        .into_iter()
+        .map(|direction| orientation * direction)
+        .collect();
+
+    // Remove any net momentum from the separation so the total momentum is conserved
+    let net_separation_momentum = directions
+        .iter()
+        .zip(mass_fractions.iter())
+        .fold(Vec3::zero(), |sum, (direction, fraction)| {
+            sum + *direction * *fraction
+        });
+
+    directions
+        .iter()
+        .zip(mass_fractions.iter())
+        .map(|(direction, fraction)| {
+            // Mass goes with the square of the radius
+            let fragment_radius = radius * fraction.sqrt();
+            Fragment {
+                offset: *direction * (radius + fragment_radius),
+                separation_velocity: (*direction - net_separation_momentum)
+                    * ASTEROID_FRAGMENT_SEPARATION_SPEED,
+                radius: fragment_radius,
+            }
+        })
+        .collect()
+}
+
+// Splits an asteroid into smaller ones with the same total mass and momentum,
+// or vaporizes it if the pieces would be too small
+pub fn fracture_asteroid(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    expl_events: &mut ResMut<Events<ExplosionEvent>>,
+    particles: &mut ResMut<Particles>,
+    entity: Entity,
+    position: Vec3,
+    velocity: Vec3,
+    radius: f32,
 ) {
     let mut rng = rand::thread_rng();
 
-    let asteroid_density = 30.0;
-    let asteroid_max_spacing = 0.7;
-    let asteroid_max_spawn_distance = 150.0;
-    let asteroid_min_radius = 0.9;
-    let asteroid_max_radius = 3.0;
-    let asteroid_relative_tangential_speed = 150.0;
-
-    let asteroids_per_axis: i32 = (asteroid_max_spawn_distance / asteroid_density) as i32;
-    let total_asteroids = asteroids_per_axis.pow(3);
-    let asteroid_max_spawn_radius = asteroid_max_spawn_distance / 2.0;
-
-    let asteroid_min_offset = -asteroid_max_spacing * asteroid_density / 2.0;
-    let asteroid_max_offset = asteroid_max_spacing * asteroid_density / 2.0;
-
-    for x in 0..total_asteroids {
-        let asteroid_offset = Vec3::new(
-            rng.gen_range(asteroid_min_offset, asteroid_max_offset),
-            rng.gen_range(asteroid_min_offset, asteroid_max_offset),
-            rng.gen_range(asteroid_min_offset, asteroid_max_offset),
-        );
-        let mut asteroid_position = Vec3::new(
-            (x % asteroids_per_axis) as f32 * asteroid_density - asteroid_max_spawn_radius,
-            ((x / asteroids_per_axis) % asteroids_per_axis) as f32 * asteroid_density
-                - asteroid_max_spawn_radius,
-            ((x / asteroids_per_axis.pow(2)) % asteroids_per_axis) as f32 * asteroid_density
-                - asteroid_max_spawn_radius,
+    commands.despawn(entity);
+
+    let fragment_count =
+        rng.gen_range(ASTEROID_MIN_FRAGMENT_COUNT, ASTEROID_MAX_FRAGMENT_COUNT + 1);
+
+    // Random share of the mass for each fragment
+    let mut mass_fractions: Vec<f32> = (0..fragment_count)
+        .map(|_| rng.gen_range(0.5, 1.0))
+        .collect();
+    let total_fraction: f32 = mass_fractions.iter().sum();
+    for fraction in mass_fractions.iter_mut() {
+        *fraction /= total_fraction;
+    }
+
+    let axis = Vec3::new(
+        rng.gen_range(-1.0, 1.0),
+        rng.gen_range(-1.0, 1.0),
+        rng.gen_range(-1.0, 1.0),
+    )
+    .normalize();
+    let orientation = Quat::from_axis_angle(axis, random_angle(&mut rng));
+    let fragments = fragments(radius, &mass_fractions, orientation);
+
+    if fragments
+        .iter()
+        .any(|fragment| fragment.radius < ASTEROID_MIN_FRAGMENT_RADIUS)
+    {
+        expl_events.send(ExplosionEvent::new(position, velocity));
+        return;
+    }
+
+    particles.burst(
+        ParticleKind::Dust,
+        position,
+        velocity,
+        Vec3::zero(),
+        (radius / ASTEROID_DUST_RADIUS).max(1.),
+    );
+
+    for fragment in fragments {
+        spawn_asteroid(
+            commands,
+            meshes,
+            materials,
+            position + fragment.offset,
+            velocity + fragment.separation_velocity,
+            fragment.radius,
         );
-        asteroid_position += asteroid_offset;
-
-        let asteroid_rotation: Vec3 = asteroid_position.normalize().cross(
-            Vec3::new(
-                rng.gen_range(0.0, 1.0),
-                rng.gen_range(0.0, 1.0),
-                rng.gen_range(0.0, 1.0),
-            )
-            .normalize(),
+    }
+}
+
+pub fn asteroid_damage_update(
+    commands: &mut Commands,
+    game_state: Res<GameState>,
+
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+
+    mut damage_event_reader: Local<EventReader<AsteroidDamageEvent>>,
+    damage_events: Res<Events<AsteroidDamageEvent>>,
+    mut expl_events: ResMut<Events<ExplosionEvent>>,
+    mut particles: ResMut<Particles>,
+
+    mut asteroid_query: Query<(&mut Asteroid, &Transform, &Velocity, &Collision)>,
+) {
+    if *game_state != GameState::Running {
+        return;
+    }
+
+    for event in damage_event_reader.iter(&damage_events) {
+        let (mut asteroid, transform, velocity, collision) =
+            match asteroid_query.get_mut(event.entity) {
+                Ok(asteroid) => asteroid,
+                _ => continue,
+            };
+
+        // Already destroyed by an earlier hit this frame
+        if asteroid.health <= 0. {
+            continue;
+        }
+
+        asteroid.health -= event.damage;
+        if asteroid.health > 0. {
+            continue;
+        }
+
+        fracture_asteroid(
+            commands,
+            &mut meshes,
+            &mut materials,
+            &mut expl_events,
+            &mut particles,
+            event.entity,
+            transform.translation,
+            velocity.velocity,
+            collision.radius,
         );
+    }
+}
+
+#[derive(Clone, Copy)]
+struct AsteroidImpactData {
+    entity: Entity,
+    health: f32,
+    position: Vec3,
+    velocity: Vec3,
+    mass: f32,
+}
 
-        let asteroid_radius = rng.gen_range(asteroid_min_radius, asteroid_max_radius);
+// Impacts the smaller asteroid can survive merge the two,
+// otherwise the smaller one breaks up and cratering damages the bigger one
+pub fn asteroid_impact_update(
+    commands: &mut Commands,
+    game_state: Res<GameState>,
 
-        let asteroid_mass = asteroid_radius * asteroid_radius;
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
 
-        let asteroid_distance = asteroid_position.length();
+    mut impact_event_reader: Local<EventReader<AsteroidImpactEvent>>,
+    impact_events: Res<Events<AsteroidImpactEvent>>,
+    mut damage_events: ResMut<Events<AsteroidDamageEvent>>,
+    mut particles: ResMut<Particles>,
 
-        commands
-            .spawn(PbrBundle {
-                mesh: meshes.add(Mesh::from(shape::Icosphere {
-                    radius: asteroid_radius,
-                    subdivisions: 4,
-                })),
-                material: materials.add(Color::rgb(0.5, 0.5, 0.5).into()),
-                transform: Transform::from_translation(asteroid_position),
-                ..Default::default()
-            })
-            .with(Gravity {
-                mass: asteroid_mass,
-            })
-            .with(Collision {
-                mass: asteroid_mass,
-                radius: asteroid_radius,
-                etype: EntityType::Asteroid,
-            })
-            .with(Velocity {
-                velocity: asteroid_rotation * asteroid_relative_tangential_speed
-                    / asteroid_distance.sqrt(),
+    asteroid_query: Query<(&Asteroid, &Transform, &Velocity, &Collision)>,
+) {
+    if *game_state != GameState::Running {
+        return;
+    }
+
+    // Asteroids that have already merged this frame
+    let mut merged_asteroids: Vec<Entity> = Vec::new();
+
+    for event in impact_event_reader.iter(&impact_events) {
+        if merged_asteroids.contains(&event.asteroid_a)
+            || merged_asteroids.contains(&event.asteroid_b)
+        {
+            continue;
+        }
+
+        let impact_data = |entity| {
+            asteroid_query
+                .get(entity)
+                .ok()
+                .map(|(a, t, v, c)| AsteroidImpactData {
+                    entity,
+                    health: a.health,
+                    position: t.translation,
+                    velocity: v.velocity,
+                    mass: c.mass,
+                })
+        };
+        let (a, b) = match (impact_data(event.asteroid_a), impact_data(event.asteroid_b)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => continue,
+        };
+        let (smaller, bigger) = if a.mass <= b.mass { (a, b) } else { (b, a) };
+
+        if event.energy < smaller.health {
+            let merged_mass = smaller.mass + bigger.mass;
+            let merged_radius = merged_mass.sqrt();
+            if merged_radius > ASTEROID_MAX_MERGED_RADIUS {
+                continue;
+            }
+
+            // Perfectly inelastic, so momentum is conserved
+            let merged_position =
+                (smaller.position * smaller.mass + bigger.position * bigger.mass) / merged_mass;
+            let merged_velocity =
+                (smaller.velocity * smaller.mass + bigger.velocity * bigger.mass) / merged_mass;
+
+            commands.despawn(smaller.entity);
+            commands.despawn(bigger.entity);
+            merged_asteroids.push(smaller.entity);
+            merged_asteroids.push(bigger.entity);
+
+            spawn_asteroid(
+                commands,
+                &mut meshes,
+                &mut materials,
+                merged_position,
+                merged_velocity,
+                merged_radius,
+            );
+            particles.burst(
+                ParticleKind::Dust,
+                merged_position,
+                merged_velocity,
+                Vec3::zero(),
+                (merged_radius / ASTEROID_DUST_RADIUS).max(1.),
+            );
+        } else if smaller.mass / bigger.mass >= ASTEROID_FRAGMENT_MASS_RATIO {
+            damage_events.send(AsteroidDamageEvent {
+                entity: smaller.entity,
+                damage: event.energy,
+            });
+            damage_events.send(AsteroidDamageEvent {
+                entity: bigger.entity,
+                damage: event.energy,
+            });
+        } else {
+            damage_events.send(AsteroidDamageEvent {
+                entity: smaller.entity,
+                damage: event.energy,
             });
+            damage_events.send(AsteroidDamageEvent {
+                entity: bigger.entity,
+                damage: event.energy * ASTEROID_CRATER_DAMAGE_SCALE,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Shares of the mass as uneven as fracture_asteroid can draw, and even ones
+    fn mass_fraction_sets(fragment_count: usize) -> Vec<Vec<f32>> {
+        let uneven: Vec<f32> = (0..fragment_count)
+            .map(|i| if i == 0 { 1. } else { 0.5 })
+            .collect();
+        let even = vec![1.; fragment_count];
+        vec![uneven, even]
+            .into_iter()
+            .map(|fractions| {
+                let total: f32 = fractions.iter().sum();
+                fractions.iter().map(|fraction| fraction / total).collect()
+            })
+            .collect()
+    }
+
+    fn orientations() -> Vec<Quat> {
+        vec![
+            Quat::identity(),
+            Quat::from_axis_angle(Vec3::unit_y(), 1.),
+            Quat::from_axis_angle(Vec3::new(1., 2., 3.).normalize(), 4.),
+        ]
+    }
+
+    #[test]
+    fn fragments_do_not_overlap() {
+        let radius = 3.;
+        for fragment_count in ASTEROID_MIN_FRAGMENT_COUNT..=ASTEROID_MAX_FRAGMENT_COUNT {
+            for mass_fractions in mass_fraction_sets(fragment_count) {
+                for orientation in orientations() {
+                    let fragments = fragments(radius, &mass_fractions, orientation);
+                    assert_eq!(fragments.len(), fragment_count);
+
+                    for (i, a) in fragments.iter().enumerate() {
+                        for b in fragments.iter().skip(i + 1) {
+                            assert!(
+                                (a.offset - b.offset).length() > a.radius + b.radius,
+                                "{} fragments overlap",
+                                fragment_count
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fragments_conserve_mass_and_momentum() {
+        let radius = 3.;
+        for fragment_count in ASTEROID_MIN_FRAGMENT_COUNT..=ASTEROID_MAX_FRAGMENT_COUNT {
+            for mass_fractions in mass_fraction_sets(fragment_count) {
+                for orientation in orientations() {
+                    let fragments = fragments(radius, &mass_fractions, orientation);
+
+                    let mass: f32 = fragments
+                        .iter()
+                        .map(|fragment| asteroid_mass(fragment.radius))
+                        .sum();
+                    assert!((mass - asteroid_mass(radius)).abs() < 1e-3);
+
+                    let momentum = fragments.iter().fold(Vec3::zero(), |sum, fragment| {
+                        sum + fragment.separation_velocity * asteroid_mass(fragment.radius)
+                    });
+                    assert!(momentum.length() < 1e-3);
+                }
+            }
+        }
     }
 }
@@ -1,22 +1,854 @@
+use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
 use bevy::prelude::*;
+use rodio::decoder::DecoderError;
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sample, Source};
+use serde::Deserialize;
+
+use crate::args::parse_f32_flags;
+use crate::input::CameraInput;
+pub use crate::sound_bank::{Sound, SoundBank};
+use crate::velocity::Velocity;
+
+// Plays sounds from where they happen, heard from the camera
+pub struct SpatialAudioPlugin {
+    pub doppler: bool,
+    pub mixer: AudioMixer,
+}
 
-use crate::cooldown::*;
+impl Plugin for SpatialAudioPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(SpatialAudio::new(self.doppler, self.mixer))
+            .add_system(audio_mixer_update)
+            .add_system_to_stage(stage::POST_UPDATE, spatial_audio_update);
+    }
+}
+
+// Sounds are at full volume up to this distance, then fall off with the inverse of distance
+pub const SOUND_REFERENCE_DISTANCE: f32 = 10.;
+// Sounds fade out completely by this distance
+pub const SOUND_MAX_DISTANCE: f32 = 400.;
+// In units per second, slower than in air so the Doppler effect is noticeable at game speeds
+pub const SPEED_OF_SOUND: f32 = 250.;
+pub const MIN_DOPPLER_SPEED: f32 = 0.5;
+pub const MAX_DOPPLER_SPEED: f32 = 2.;
+// How far sounds can be panned to one side, 1 for only one ear
+pub const MAX_PAN: f32 = 0.8;
+// Limits on the pitch a sound can be played at, as a playback speed
+pub const MIN_PITCH: f32 = 0.25;
+pub const MAX_PITCH: f32 = 4.;
+
+// Sounds past this many are dropped, or take over from a lower priority sound
+pub const MAX_VOICES: usize = 32;
+pub const MUSIC_CROSSFADE_DURATION: f32 = 3.;
+
+// Gains and speed are updated at the start of every block of this many frames
+const SOUND_BLOCK_FRAMES: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum AudioBus {
+    Music,
+    Sfx,
+    Ui,
+}
 
-pub fn play_sound(asset_server: &Res<AssetServer>, audio: &Res<Audio>, sound_effect_name: &str) {
-    let sound_effect = asset_server.load(sound_effect_name);
-    audio.play(sound_effect);
+// Volumes from 0 to 1 for each bus, which the master volume scales
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioMixer {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub ui: f32,
+    // Toggled with M
+    pub muted: bool,
+}
+
+impl Default for AudioMixer {
+    fn default() -> Self {
+        AudioMixer {
+            master: 1.,
+            music: 0.7,
+            sfx: 1.,
+            ui: 1.,
+            muted: false,
+        }
+    }
+}
+
+impl AudioMixer {
+    // Volumes are set with e.g. -music-volume=0.5
+    pub fn from_args(args: &[String]) -> AudioMixer {
+        let mut mixer = AudioMixer::default();
+
+        parse_f32_flags(
+            args,
+            &mut [
+                ("-master-volume", &mut mixer.master),
+                ("-music-volume", &mut mixer.music),
+                ("-sfx-volume", &mut mixer.sfx),
+                ("-ui-volume", &mut mixer.ui),
+            ],
+        );
+        // Volumes go up to full, with no boosting
+        mixer.master = mixer.master.min(1.);
+        mixer.music = mixer.music.min(1.);
+        mixer.sfx = mixer.sfx.min(1.);
+        mixer.ui = mixer.ui.min(1.);
+
+        mixer
+    }
+
+    pub fn volume(&self, bus: AudioBus) -> f32 {
+        if self.muted {
+            return 0.;
+        }
+        let bus_volume = match bus {
+            AudioBus::Music => self.music,
+            AudioBus::Sfx => self.sfx,
+            AudioBus::Ui => self.ui,
+        };
+        self.master * bus_volume
+    }
 }
 
-pub const SOUNDTRACK_DURATION: f64 = 0.8;
-pub fn play_soundtrack(
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+// How a sound plays, from the sound bank data file
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SoundEffect {
+    pub bus: AudioBus,
+    pub volume: f32,
+    // When there are too many voices, higher priority sounds take over from lower ones
+    pub priority: u32,
+    // How many of this sound can play at once, any more are dropped
+    pub max_voices: usize,
+    pub looping: bool,
+}
+
+// Most sounds are one-off effects
+impl Default for SoundEffect {
+    fn default() -> Self {
+        SoundEffect {
+            bus: AudioBus::Sfx,
+            volume: 1.,
+            priority: 1,
+            max_voices: 4,
+            looping: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SoundPosition {
+    // Heard the same wherever the listener is, like music and the player's own ship
+    Global,
+    // Where something happened, like an explosion
+    At { position: Vec3, velocity: Vec3 },
+    // Follows an entity for as long as it exists, then stays where it was last
+    Entity(Entity),
+}
+
+// Shared with the audio thread, which reads them for every block of samples
+struct SoundControls {
+    left_gain: AtomicU32,
+    right_gain: AtomicU32,
+    speed: AtomicU32,
+    // Set by the game to hold a sound where it is, playing silence
+    paused: AtomicBool,
+    // Set by the game to cut a sound off
+    stopped: AtomicBool,
+    // Set by the audio thread when a sound has played out
+    finished: AtomicBool,
+}
+
+fn store_f32(atomic: &AtomicU32, value: f32) {
+    atomic.store(value.to_bits(), Ordering::Relaxed);
+}
+
+fn load_f32(atomic: &AtomicU32) -> f32 {
+    f32::from_bits(atomic.load(Ordering::Relaxed))
+}
+
+impl SoundControls {
+    fn new(left_gain: f32, right_gain: f32, speed: f32) -> SoundControls {
+        SoundControls {
+            left_gain: AtomicU32::new(left_gain.to_bits()),
+            right_gain: AtomicU32::new(right_gain.to_bits()),
+            speed: AtomicU32::new(speed.to_bits()),
+            paused: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+        }
+    }
+
+    fn set(&self, left_gain: f32, right_gain: f32, speed: f32) {
+        store_f32(&self.left_gain, left_gain);
+        store_f32(&self.right_gain, right_gain);
+        store_f32(&self.speed, speed);
+    }
+
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    fn done(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed) || self.finished.load(Ordering::Relaxed)
+    }
+}
+
+type SoundDecoder = Decoder<Cursor<Arc<[u8]>>>;
+
+// Decodes a sound over and over, for music and other loops
+struct LoopingDecoder {
+    bytes: Arc<[u8]>,
+    decoder: SoundDecoder,
+}
+
+impl LoopingDecoder {
+    fn new(bytes: Arc<[u8]>) -> Result<LoopingDecoder, DecoderError> {
+        let decoder = Decoder::new(Cursor::new(bytes.clone()))?;
+        Ok(LoopingDecoder { bytes, decoder })
+    }
+}
+
+impl Iterator for LoopingDecoder {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if let Some(sample) = self.decoder.next() {
+            return Some(sample);
+        }
+        self.decoder = Decoder::new(Cursor::new(self.bytes.clone())).ok()?;
+        self.decoder.next()
+    }
+}
+
+impl Source for LoopingDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.decoder.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.decoder.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.decoder.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// Plays a source in stereo, with gains and speed that can change while it plays.
+// The speed is applied by changing the sample rate reported for each block.
+struct SpatialSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    input: S,
+    controls: Arc<SoundControls>,
+    // Mixes stereo down to mono before panning, so the position decides the balance
+    downmix: bool,
+
+    // Gains ramp across each block so changes don't click
+    left_gain: f32,
+    right_gain: f32,
+    left_step: f32,
+    right_step: f32,
+    speed: f32,
+    block_frames_left: usize,
+
+    // The right half of the current frame, played after the left
+    right_sample: Option<f32>,
+}
+
+impl<S> SpatialSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn new(input: S, controls: Arc<SoundControls>, downmix: bool) -> SpatialSource<S> {
+        let mut source = SpatialSource {
+            input,
+            left_gain: load_f32(&controls.left_gain),
+            right_gain: load_f32(&controls.right_gain),
+            controls,
+            downmix,
+            left_step: 0.,
+            right_step: 0.,
+            speed: 1.,
+            block_frames_left: 0,
+            right_sample: None,
+        };
+        source.start_block();
+        source
+    }
+
+    fn start_block(&mut self) {
+        let blocks = SOUND_BLOCK_FRAMES as f32;
+        self.left_step = (load_f32(&self.controls.left_gain) - self.left_gain) / blocks;
+        self.right_step = (load_f32(&self.controls.right_gain) - self.right_gain) / blocks;
+        self.speed = load_f32(&self.controls.speed);
+        self.block_frames_left = SOUND_BLOCK_FRAMES;
+    }
+
+    fn finish(&mut self) -> Option<f32> {
+        self.controls.finished.store(true, Ordering::Relaxed);
+        None
+    }
+}
+
+impl<S> Iterator for SpatialSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.right_sample.take() {
+            // Start the next block here so its sample rate is reported before it plays
+            if self.block_frames_left == 0 {
+                self.start_block();
+            }
+            return Some(right);
+        }
+
+        if self.controls.stopped.load(Ordering::Relaxed) {
+            return self.finish();
+        }
+
+        let (left, right) = if self.controls.paused.load(Ordering::Relaxed) {
+            (0., 0.)
+        } else {
+            let channels = self.input.channels().max(1);
+            let left = match self.input.next() {
+                Some(sample) => sample.to_f32(),
+                None => return self.finish(),
+            };
+            let right = if channels > 1 {
+                match self.input.next() {
+                    Some(sample) => sample.to_f32(),
+                    None => return self.finish(),
+                }
+            } else {
+                left
+            };
+            // Anything past stereo is dropped
+            for _ in 2..channels {
+                self.input.next();
+            }
+
+            if self.downmix {
+                let mono = (left + right) / 2.;
+                (mono, mono)
+            } else {
+                (left, right)
+            }
+        };
+
+        self.left_gain += self.left_step;
+        self.right_gain += self.right_step;
+        self.block_frames_left -= 1;
+
+        self.right_sample = Some(right * self.right_gain);
+        Some(left * self.left_gain)
+    }
+}
+
+impl<S> Source for SpatialSource<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        let pending = if self.right_sample.is_some() { 1 } else { 0 };
+        Some(self.block_frames_left * 2 + pending)
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        ((self.input.sample_rate() as f32 * self.speed) as u32).max(1)
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// Identifies a sound that was played, to change it while it plays
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SoundId(u64);
+
+enum SoundAdjustment {
+    Volume(f32),
+    Pitch(f32),
+    Paused(bool),
+    // Over this many seconds, then stops
+    FadeOut(f32),
+}
+
+struct QueuedSound {
+    id: SoundId,
+    sound: Sound,
+    handle: Handle<AudioSource>,
+    effect: SoundEffect,
+    position: SoundPosition,
+    volume: f32,
+    pitch: f32,
+    paused: bool,
+    // Fades in while other music fades out
+    crossfade: bool,
+}
+
+struct PlayingSound {
+    id: SoundId,
+    sound: Sound,
+    controls: Arc<SoundControls>,
+    effect: SoundEffect,
+    // Scales the effect's volume and playback speed for this sound only
+    volume: f32,
+    pitch: f32,
+    position: SoundPosition,
+    // Where an entity's sound was last heard from, in case the entity is gone
+    last_position: Vec3,
+    last_velocity: Vec3,
+    // From 0 to 1, changing by the rate every second, for crossfades
+    fade: f32,
+    fade_rate: f32,
+    // How loud it was last heard, to pick the quietest voice to drop
+    loudness: f32,
+}
+
+impl PlayingSound {
+    fn fading_out(&self) -> bool {
+        self.fade_rate < 0.
+    }
+}
+
+pub struct SpatialAudio {
+    output: Option<OutputStreamHandle>,
+    pub doppler: bool,
+    pub mixer: AudioMixer,
+    next_id: AtomicU64,
+    queued: Mutex<Vec<QueuedSound>>,
+    adjustments: Mutex<Vec<(SoundId, SoundAdjustment)>>,
+    playing: Vec<PlayingSound>,
+}
+
+// The output stream stops when it is dropped and can't be sent between threads,
+// so it is kept alive on a thread of its own
+fn open_audio_output() -> Option<OutputStreamHandle> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || match OutputStream::try_default() {
+        Ok((_stream, handle)) => {
+            if sender.send(Some(handle)).is_ok() {
+                loop {
+                    std::thread::park();
+                }
+            }
+        }
+        Err(error) => {
+            eprintln!("No audio output, sounds are off: {:?}", error);
+            let _ = sender.send(None);
+        }
+    });
+    receiver.recv().ok().flatten()
+}
+
+impl SpatialAudio {
+    pub fn new(doppler: bool, mixer: AudioMixer) -> SpatialAudio {
+        SpatialAudio {
+            output: open_audio_output(),
+            doppler,
+            mixer,
+            next_id: AtomicU64::new(0),
+            queued: Mutex::new(Vec::new()),
+            adjustments: Mutex::new(Vec::new()),
+            playing: Vec::new(),
+        }
+    }
+
+    // Sounds are queued until they have loaded
+    pub fn play(
+        &self,
+        sound: Sound,
+        handle: Handle<AudioSource>,
+        effect: SoundEffect,
+        position: SoundPosition,
+    ) -> SoundId {
+        self.queue(sound, handle, effect, position, false)
+    }
+
+    // Fades music in while any other music fades out
+    pub fn play_music(
+        &self,
+        sound: Sound,
+        handle: Handle<AudioSource>,
+        effect: SoundEffect,
+    ) -> SoundId {
+        self.queue(sound, handle, effect, SoundPosition::Global, true)
+    }
+
+    fn queue(
+        &self,
+        sound: Sound,
+        handle: Handle<AudioSource>,
+        effect: SoundEffect,
+        position: SoundPosition,
+        crossfade: bool,
+    ) -> SoundId {
+        let id = SoundId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.queued.lock().unwrap().push(QueuedSound {
+            id,
+            sound,
+            handle,
+            effect,
+            position,
+            volume: 1.,
+            pitch: 1.,
+            paused: false,
+            crossfade,
+        });
+        id
+    }
+
+    // From 0 to 1, on top of the sound effect's own volume
+    pub fn set_volume(&self, id: SoundId, volume: f32) {
+        let adjustment = SoundAdjustment::Volume(volume.max(0.).min(1.));
+        self.adjustments.lock().unwrap().push((id, adjustment));
+    }
+
+    // Playback speed, which raises or lowers the pitch along with it
+    pub fn set_pitch(&self, id: SoundId, pitch: f32) {
+        let adjustment = SoundAdjustment::Pitch(pitch.max(MIN_PITCH).min(MAX_PITCH));
+        self.adjustments.lock().unwrap().push((id, adjustment));
+    }
+
+    // A paused sound keeps its voice and carries on from where it was
+    pub fn set_paused(&self, id: SoundId, paused: bool) {
+        let adjustment = SoundAdjustment::Paused(paused);
+        self.adjustments.lock().unwrap().push((id, adjustment));
+    }
+
+    pub fn fade_out(&self, id: SoundId, duration: f32) {
+        let adjustment = SoundAdjustment::FadeOut(duration);
+        self.adjustments.lock().unwrap().push((id, adjustment));
+    }
+
+    // Makes room for a sound if it can play, following the voice limits
+    fn make_room_for(&mut self, sound: Sound, effect: &SoundEffect) -> bool {
+        // Sounds fading out are on their way out, so they don't count
+        let same_voices = self
+            .playing
+            .iter()
+            .filter(|playing| playing.sound == sound && !playing.fading_out())
+            .count();
+        if same_voices >= effect.max_voices {
+            return false;
+        }
+
+        if self.playing.len() < MAX_VOICES {
+            return true;
+        }
+
+        // Take over from the quietest of the lowest priority sounds
+        let lowest = self
+            .playing
+            .iter()
+            .enumerate()
+            .min_by(|(_, s1), (_, s2)| {
+                s1.effect
+                    .priority
+                    .cmp(&s2.effect.priority)
+                    .then(s1.loudness.partial_cmp(&s2.loudness).unwrap())
+            })
+            .map(|(i, sound)| (i, sound.effect.priority));
+        match lowest {
+            Some((i, priority)) if priority < effect.priority => {
+                self.playing.remove(i).controls.stop();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+pub fn play_sound(sound_bank: &Res<SoundBank>, audio: &Res<SpatialAudio>, sound: Sound) -> SoundId {
+    play_sound_from(sound_bank, audio, sound, SoundPosition::Global)
+}
+
+pub fn play_sound_at(
+    sound_bank: &Res<SoundBank>,
+    audio: &Res<SpatialAudio>,
+    sound: Sound,
+    position: Vec3,
+    velocity: Vec3,
+) -> SoundId {
+    play_sound_from(
+        sound_bank,
+        audio,
+        sound,
+        SoundPosition::At { position, velocity },
+    )
+}
+
+pub fn play_sound_from(
+    sound_bank: &Res<SoundBank>,
+    audio: &Res<SpatialAudio>,
+    sound: Sound,
+    position: SoundPosition,
+) -> SoundId {
+    let (handle, effect) = sound_bank.pick(sound);
+    audio.play(sound, handle, effect, position)
+}
+
+pub fn play_music(sound_bank: &Res<SoundBank>, audio: &Res<SpatialAudio>, music: Sound) -> SoundId {
+    let (handle, effect) = sound_bank.pick(music);
+    audio.play_music(music, handle, effect)
+}
+
+struct Listener {
+    position: Vec3,
+    rotation: Quat,
+    velocity: Vec3,
+}
+
+// Left gain, right gain and playback speed of a sound heard by the listener
+fn spatialize(
+    listener: &Listener,
+    position: Vec3,
+    velocity: Vec3,
+    doppler: bool,
+) -> (f32, f32, f32) {
+    let to_sound = position - listener.position;
+    let distance = to_sound.length();
+
+    let attenuation = SOUND_REFERENCE_DISTANCE / distance.max(SOUND_REFERENCE_DISTANCE);
+    let fade = (1. - distance / SOUND_MAX_DISTANCE).max(0.);
+    let gain = attenuation * fade;
+
+    if distance <= 0. {
+        return (gain, gain, 1.);
+    }
+    let direction = to_sound / distance;
+
+    // Equal power panning, relative to the listener who looks down the negative-z axis
+    let local_direction = listener.rotation.conjugate() * direction;
+    let pan = local_direction.x * MAX_PAN;
+    let angle = (pan + 1.) * std::f32::consts::PI / 4.;
+    let (left, right) = (angle.cos(), angle.sin());
+    let centre = std::f32::consts::FRAC_1_SQRT_2;
+
+    let speed = if doppler {
+        let listener_approach = listener.velocity.dot(direction);
+        let sound_approach = -velocity.dot(direction);
+        ((SPEED_OF_SOUND + listener_approach) / (SPEED_OF_SOUND - sound_approach).max(1.))
+            .max(MIN_DOPPLER_SPEED)
+            .min(MAX_DOPPLER_SPEED)
+    } else {
+        1.
+    };
+
+    (gain * left / centre, gain * right / centre, speed)
+}
+
+// Left gain, right gain and playback speed of a sound, with its volume and fade
+fn sound_gains(
+    listener: &Listener,
+    sound: &PlayingSound,
+    mixer: &AudioMixer,
+    doppler: bool,
+) -> (f32, f32, f32) {
+    let (left, right, speed) = match sound.position {
+        SoundPosition::Global => (1., 1., 1.),
+        _ => spatialize(listener, sound.last_position, sound.last_velocity, doppler),
+    };
+    let volume = sound.effect.volume * sound.volume * mixer.volume(sound.effect.bus) * sound.fade;
+    (left * volume, right * volume, speed * sound.pitch)
+}
+
+fn audio_mixer_update(key_input: Res<Input<KeyCode>>, mut audio: ResMut<SpatialAudio>) {
+    if key_input.just_pressed(KeyCode::M) {
+        audio.mixer.muted = !audio.mixer.muted;
+    }
+}
+
+fn spatial_audio_update(
     time: Res<Time>,
-    mut soundtrack_cooldown: Local<Cooldown>,
+    mut audio: ResMut<SpatialAudio>,
+    sources: Res<Assets<AudioSource>>,
+    listener_query: Query<(&CameraInput, &GlobalTransform)>,
+    emitter_query: Query<(&GlobalTransform, Option<&Velocity>)>,
+    mut last_listener_position: Local<Option<Vec3>>,
 ) {
-    if soundtrack_cooldown.over(&time) {
-        let music = asset_server.load("audio/Asteroid_Game_Soundtrack.mp3");
-        audio.play(music);
-        soundtrack_cooldown.reset(&time, SOUNDTRACK_DURATION);
+    let listener = match listener_query.iter().next() {
+        Some((_, transform)) => {
+            let velocity = match *last_listener_position {
+                Some(last) if time.delta_seconds > 0. => {
+                    (transform.translation - last) / time.delta_seconds
+                }
+                _ => Vec3::zero(),
+            };
+            Listener {
+                position: transform.translation,
+                rotation: transform.rotation,
+                velocity,
+            }
+        }
+        None => Listener {
+            position: Vec3::zero(),
+            rotation: Quat::identity(),
+            velocity: Vec3::zero(),
+        },
+    };
+    *last_listener_position = Some(listener.position);
+
+    let doppler = audio.doppler;
+    let mixer = audio.mixer;
+    let emitter = |position: SoundPosition| match position {
+        SoundPosition::Global => None,
+        SoundPosition::At { position, velocity } => Some((position, velocity)),
+        SoundPosition::Entity(entity) => emitter_query.get(entity).ok().map(|(t, v)| {
+            let velocity = v.map(|v| v.velocity).unwrap_or_else(Vec3::zero);
+            (t.translation, velocity)
+        }),
+    };
+
+    let mut queued: Vec<QueuedSound> = audio.queued.lock().unwrap().drain(..).collect();
+
+    // Changes can be made before a sound has loaded as well as while it plays
+    let adjustments: Vec<(SoundId, SoundAdjustment)> =
+        audio.adjustments.lock().unwrap().drain(..).collect();
+    for (id, adjustment) in adjustments {
+        if let Some(sound) = audio.playing.iter_mut().find(|sound| sound.id == id) {
+            match adjustment {
+                SoundAdjustment::Volume(volume) => sound.volume = volume,
+                SoundAdjustment::Pitch(pitch) => sound.pitch = pitch,
+                SoundAdjustment::Paused(paused) => {
+                    sound.controls.paused.store(paused, Ordering::Relaxed);
+                }
+                SoundAdjustment::FadeOut(duration) => {
+                    sound.fade_rate = -1. / duration.max(0.001);
+                }
+            }
+        } else if let Some(index) = queued.iter().position(|sound| sound.id == id) {
+            match adjustment {
+                SoundAdjustment::Volume(volume) => queued[index].volume = volume,
+                SoundAdjustment::Pitch(pitch) => queued[index].pitch = pitch,
+                SoundAdjustment::Paused(paused) => queued[index].paused = paused,
+                SoundAdjustment::FadeOut(_) => {
+                    queued.remove(index);
+                }
+            }
+        }
+    }
+
+    // Start the sounds that have loaded
+    let mut still_loading = Vec::new();
+    for queued_sound in queued {
+        let source = match sources.get(&queued_sound.handle) {
+            Some(source) => source,
+            None => {
+                still_loading.push(queued_sound);
+                continue;
+            }
+        };
+        let output = match &audio.output {
+            Some(output) => output.clone(),
+            None => continue,
+        };
+
+        let effect = queued_sound.effect;
+        let crossfade = queued_sound.crossfade;
+        if !audio.make_room_for(queued_sound.sound, &effect) {
+            continue;
+        }
+
+        // New music fades in over the old, but not over music asked for after it
+        let fade = if crossfade {
+            for sound in audio.playing.iter_mut() {
+                if sound.effect.bus == AudioBus::Music
+                    && sound.effect.looping
+                    && sound.id < queued_sound.id
+                {
+                    sound.fade_rate = -1. / MUSIC_CROSSFADE_DURATION;
+                }
+            }
+            0.
+        } else {
+            1.
+        };
+
+        let (last_position, last_velocity) =
+            emitter(queued_sound.position).unwrap_or((listener.position, Vec3::zero()));
+        let mut sound = PlayingSound {
+            id: queued_sound.id,
+            sound: queued_sound.sound,
+            controls: Arc::new(SoundControls::new(0., 0., 1.)),
+            effect,
+            volume: queued_sound.volume,
+            pitch: queued_sound.pitch,
+            position: queued_sound.position,
+            last_position,
+            last_velocity,
+            fade,
+            fade_rate: if crossfade {
+                1. / MUSIC_CROSSFADE_DURATION
+            } else {
+                0.
+            },
+            loudness: 0.,
+        };
+        let (left_gain, right_gain, speed) = sound_gains(&listener, &sound, &mixer, doppler);
+        sound.controls.set(left_gain, right_gain, speed);
+        sound
+            .controls
+            .paused
+            .store(queued_sound.paused, Ordering::Relaxed);
+
+        let downmix = sound.position != SoundPosition::Global;
+        let played = if effect.looping {
+            LoopingDecoder::new(source.bytes.clone()).map(|decoder| {
+                output.play_raw(SpatialSource::new(decoder, sound.controls.clone(), downmix))
+            })
+        } else {
+            Decoder::new(Cursor::new(source.bytes.clone())).map(|decoder| {
+                output.play_raw(SpatialSource::new(decoder, sound.controls.clone(), downmix))
+            })
+        };
+        match played {
+            Ok(Ok(())) => audio.playing.push(sound),
+            Ok(Err(error)) => eprintln!("Could not play {:?}: {:?}", queued_sound.sound, error),
+            Err(error) => eprintln!("Could not decode {:?}: {:?}", queued_sound.sound, error),
+        }
+    }
+    audio.queued.lock().unwrap().extend(still_loading);
+
+    // Move the sounds that are playing to where they are heard from now
+    for sound in audio.playing.iter_mut() {
+        sound.fade = (sound.fade + sound.fade_rate * time.delta_seconds)
+            .max(0.)
+            .min(1.);
+        if sound.fading_out() && sound.fade <= 0. {
+            sound.controls.stop();
+        }
+
+        if let Some((position, velocity)) = emitter(sound.position) {
+            sound.last_position = position;
+            sound.last_velocity = velocity;
+        }
+        let (left_gain, right_gain, speed) = sound_gains(&listener, sound, &mixer, doppler);
+        sound.loudness = (left_gain + right_gain) / 2.;
+        sound.controls.set(left_gain, right_gain, speed);
     }
+    audio.playing.retain(|sound| !sound.controls.done());
 }
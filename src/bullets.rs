@@ -1,26 +1,237 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
 
-use crate::audio::play_sound;
+use crate::audio::*;
 use crate::collision::*;
 use crate::gravity::*;
 use crate::velocity::*;
+use crate::GameState;
 
-struct Bullet;
+pub struct Bullet {
+    // Seconds until the bullet is cleaned up
+    pub expires_in: f32,
+}
 
 // Relative to the shooter
 pub const RELATIVE_BULLET_SPEED: f32 = 120.;
 pub const BULLET_RADIUS: f32 = 0.15;
 pub const BULLET_MASS: f32 = 0.001;
+pub const BULLET_LIFETIME: f32 = 3.;
+pub const BULLET_RANGE: f32 = 300.;
+
+#[derive(Clone, Copy)]
+pub struct ProjectileParameters {
+    // Relative to the shooter
+    pub speed: f32,
+    pub radius: f32,
+    pub mass: f32,
+    pub color: Color,
+    pub etype: EntityType,
+    // The projectile expires after whichever of these runs out first
+    pub lifetime: f32,
+    pub range: f32,
+}
+
+impl ProjectileParameters {
+    fn expires_in(&self) -> f32 {
+        if self.speed == 0. {
+            return self.lifetime;
+        }
+        self.lifetime.min(self.range / self.speed.abs())
+    }
+}
+
+pub fn bullet_parameters() -> ProjectileParameters {
+    ProjectileParameters {
+        speed: RELATIVE_BULLET_SPEED,
+        radius: BULLET_RADIUS,
+        mass: BULLET_MASS,
+        color: Color::rgb(1.0, 0.2, 0.2),
+        etype: EntityType::Bullet,
+        lifetime: BULLET_LIFETIME,
+        range: BULLET_RANGE,
+    }
+}
+
+// Projectiles that look the same share a mesh and material
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct ProjectileAssetKey {
+    radius: u32,
+    color: [u32; 4],
+}
+
+impl ProjectileAssetKey {
+    fn new(projectile: &ProjectileParameters) -> ProjectileAssetKey {
+        let color = projectile.color;
+        ProjectileAssetKey {
+            radius: projectile.radius.to_bits(),
+            color: [
+                color.r().to_bits(),
+                color.g().to_bits(),
+                color.b().to_bits(),
+                color.a().to_bits(),
+            ],
+        }
+    }
+}
+
+pub struct BulletSpawner {
+    assets: HashMap<ProjectileAssetKey, (Handle<Mesh>, Handle<StandardMaterial>)>,
+    missile_assets: HashMap<ProjectileAssetKey, (Handle<Mesh>, Handle<StandardMaterial>)>,
+
+    // Despawned bullets are hidden and reused rather than despawned
+    pub pooling_enabled: bool,
+    // Along with the time they were released, so they aren't reused in the same frame
+    pool: Vec<(Entity, f64)>,
+}
+
+impl BulletSpawner {
+    pub fn new(pooling_enabled: bool) -> BulletSpawner {
+        BulletSpawner {
+            assets: HashMap::new(),
+            missile_assets: HashMap::new(),
+            pooling_enabled,
+            pool: Vec::new(),
+        }
+    }
+
+    fn assets(
+        &mut self,
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+        projectile: &ProjectileParameters,
+    ) -> (Handle<Mesh>, Handle<StandardMaterial>) {
+        let (mesh, material) = self
+            .assets
+            .entry(ProjectileAssetKey::new(projectile))
+            .or_insert_with(|| {
+                let mesh = meshes.add(Mesh::from(shape::Icosphere {
+                    radius: projectile.radius,
+                    ..Default::default()
+                }));
+                let material = materials.add(projectile.color.into());
+                (mesh, material)
+            });
+        (mesh.clone(), material.clone())
+    }
+
+    // Missiles aren't pooled, but share their elongated mesh and material the same way
+    pub fn missile_assets(
+        &mut self,
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+        projectile: &ProjectileParameters,
+    ) -> (Handle<Mesh>, Handle<StandardMaterial>) {
+        let (mesh, material) = self
+            .missile_assets
+            .entry(ProjectileAssetKey::new(projectile))
+            .or_insert_with(|| {
+                let mesh = meshes.add(Mesh::from(shape::Box::new(
+                    projectile.radius,
+                    projectile.radius,
+                    projectile.radius * 3.,
+                )));
+                let material = materials.add(projectile.color.into());
+                (mesh, material)
+            });
+        (mesh.clone(), material.clone())
+    }
+
+    fn spawn(
+        &mut self,
+        commands: &mut Commands,
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+        time: &Time,
+        projectile: &ProjectileParameters,
+        position: Vec3,
+        velocity: Vec3,
+    ) {
+        let (mesh, material) = self.assets(meshes, materials, projectile);
+
+        let components = (
+            Bullet {
+                expires_in: projectile.expires_in(),
+            },
+            Gravity {
+                mass: projectile.mass,
+            },
+            Collision {
+                mass: projectile.mass,
+                radius: projectile.radius,
+                etype: projectile.etype,
+            },
+            Velocity { velocity },
+        );
+
+        let reusable_index = self
+            .pool
+            .iter()
+            .position(|(_, released_seconds)| *released_seconds < time.seconds_since_startup);
+
+        match reusable_index {
+            Some(index) => {
+                let (entity, _) = self.pool.swap_remove(index);
+                commands.insert(
+                    entity,
+                    (
+                        mesh,
+                        material,
+                        Transform::from_translation(position),
+                        Visible::default(),
+                    ),
+                );
+                commands.insert(entity, components);
+            }
+            None => {
+                commands
+                    .spawn(PbrBundle {
+                        mesh,
+                        material,
+                        transform: Transform::from_translation(position),
+                        ..Default::default()
+                    })
+                    .with_bundle(components);
+            }
+        }
+    }
+
+    // Use instead of despawning entities spawned by fire_projectile
+    pub fn despawn(&mut self, commands: &mut Commands, time: &Time, entity: Entity) {
+        if !self.pooling_enabled {
+            commands.despawn(entity);
+            return;
+        }
+
+        // A bullet can be despawned more than once in a frame if it hits several things
+        if self.pool.iter().any(|(e, _)| *e == entity) {
+            return;
+        }
+
+        commands.remove::<(Bullet, Gravity, Collision, Velocity)>(entity);
+        commands.insert_one(
+            entity,
+            Visible {
+                is_visible: false,
+                ..Default::default()
+            },
+        );
+        self.pool.push((entity, time.seconds_since_startup));
+    }
+}
 
 pub fn fire_bullet(
     // Systems needed to spawn the bullet
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
+    bullet_spawner: &mut ResMut<BulletSpawner>,
+    time: &Time,
 
     // To play sound effect
-    asset_server: &Res<AssetServer>,
-    audio: &Res<Audio>,
+    sound_bank: &Res<SoundBank>,
+    audio: &Res<SpatialAudio>,
 
     // Info to spawn the bullet
     shooter_position: Vec3,
@@ -28,29 +239,74 @@ pub fn fire_bullet(
     shooter_facing: Vec3,
     shooter_barrel_length: f32,
 ) {
-    play_sound(&asset_server, &audio, "audio/Laser3.mp3");
+    play_sound_at(
+        &sound_bank,
+        &audio,
+        Sound::Laser,
+        shooter_position,
+        shooter_velocity,
+    );
 
-    let bullet_velocity = shooter_velocity + RELATIVE_BULLET_SPEED * shooter_facing;
-    let bullet_position = shooter_position + shooter_barrel_length * shooter_facing;
+    fire_projectile(
+        commands,
+        meshes,
+        materials,
+        bullet_spawner,
+        time,
+        &bullet_parameters(),
+        shooter_position,
+        shooter_velocity,
+        shooter_facing,
+        shooter_barrel_length,
+    );
+}
 
-    commands
-        .spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Icosphere {
-                radius: BULLET_RADIUS,
-                ..Default::default()
-            })),
-            material: materials.add(Color::rgb(1.0, 0.2, 0.2).into()),
-            transform: Transform::from_translation(bullet_position),
-            ..Default::default()
-        })
-        .with(Bullet)
-        .with(Gravity { mass: BULLET_MASS })
-        .with(Collision {
-            mass: BULLET_MASS,
-            radius: BULLET_RADIUS,
-            etype: EntityType::Bullet,
-        })
-        .with(Velocity {
-            velocity: bullet_velocity,
-        });
+pub fn fire_projectile(
+    // Systems needed to spawn the projectile
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    bullet_spawner: &mut ResMut<BulletSpawner>,
+    time: &Time,
+
+    // Info to spawn the projectile
+    projectile: &ProjectileParameters,
+    shooter_position: Vec3,
+    shooter_velocity: Vec3,
+    shooter_facing: Vec3,
+    shooter_barrel_length: f32,
+) {
+    // Launch clear of the shooter so big projectiles like mines don't start inside it
+    let launch_distance = shooter_barrel_length + projectile.radius;
+    let bullet_velocity = shooter_velocity + projectile.speed * shooter_facing;
+    let bullet_position = shooter_position + launch_distance * shooter_facing;
+
+    bullet_spawner.spawn(
+        commands,
+        meshes,
+        materials,
+        time,
+        projectile,
+        bullet_position,
+        bullet_velocity,
+    );
+}
+
+pub fn bullets_update(
+    commands: &mut Commands,
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    mut bullet_spawner: ResMut<BulletSpawner>,
+    mut bullet_query: Query<(Entity, &mut Bullet)>,
+) {
+    if *game_state != GameState::Running {
+        return;
+    }
+
+    for (entity, mut bullet) in bullet_query.iter_mut() {
+        bullet.expires_in -= time.delta_seconds;
+        if bullet.expires_in <= 0. {
+            bullet_spawner.despawn(commands, &time, entity);
+        }
+    }
 }
@@ -0,0 +1,286 @@
+use bevy::prelude::*;
+
+use crate::collision::*;
+use crate::input::{CameraInput, PlayerInput};
+use crate::targeting::TargetLock;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(CameraController::default())
+            .add_system(camera_mode_update)
+            .add_system(camera_update);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraMode {
+    // Behind and above the ship, following it on a spring
+    Chase,
+    Cockpit,
+    // Circling the locked target, or the ship if nothing is locked
+    Orbit,
+    // Detached from the ship and flown with the arrow keys, page up and page down
+    Free,
+}
+
+// In the order C cycles through them
+pub const CAMERA_MODES: [CameraMode; 4] = [
+    CameraMode::Chase,
+    CameraMode::Cockpit,
+    CameraMode::Orbit,
+    CameraMode::Free,
+];
+
+pub const CHASE_DISTANCE: f32 = 10.;
+pub const CHASE_HEIGHT: f32 = 2.5;
+// Relative to the ship's centre, in the ship's frame
+pub const COCKPIT_HEIGHT: f32 = 0.6;
+pub const COCKPIT_FORWARD: f32 = 1.;
+pub const ORBIT_DISTANCE: f32 = 25.;
+pub const ORBIT_HEIGHT: f32 = 6.;
+// Radians per second
+pub const ORBIT_RATE: f32 = 0.3;
+pub const FREE_CAMERA_SPEED: f32 = 40.;
+pub const FREE_CAMERA_BOOST: f32 = 5.;
+
+// The follow spring is critically damped, so it settles as fast as it can without overshooting
+pub const CAMERA_SPRING_STIFFNESS: f32 = 60.;
+// How quickly the camera turns to match the ship, higher is faster
+pub const CAMERA_ROTATION_SHARPNESS: f32 = 10.;
+// The camera is kept at least this far from the surface of anything in the way
+pub const CAMERA_COLLISION_MARGIN: f32 = 0.5;
+
+pub struct CameraController {
+    pub mode: CameraMode,
+
+    // From the thing being followed to the camera, springing towards where it should be
+    offset: Vec3,
+    offset_velocity: Vec3,
+
+    orbit_angle: f32,
+
+    // The free camera keeps its own place and rotation
+    pub free_position: Vec3,
+    pub free_rotation: Quat,
+
+    // Where the camera would be without any shake
+    pub position: Vec3,
+    pub rotation: Quat,
+
+    // Set by the camera effects, in the camera's frame
+    pub shake_offset: Vec3,
+    pub shake_rotation: Quat,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController {
+            mode: CameraMode::Chase,
+            offset: Vec3::new(0., CHASE_HEIGHT, CHASE_DISTANCE),
+            offset_velocity: Vec3::zero(),
+            orbit_angle: 0.,
+            free_position: Vec3::zero(),
+            free_rotation: Quat::identity(),
+            position: Vec3::zero(),
+            rotation: Quat::identity(),
+            shake_offset: Vec3::zero(),
+            shake_rotation: Quat::identity(),
+        }
+    }
+}
+
+impl CameraController {
+    // Moves the offset towards where it should be, like a critically damped spring
+    fn spring_offset(&mut self, desired_offset: Vec3, delta_seconds: f32) -> Vec3 {
+        let damping = 2. * CAMERA_SPRING_STIFFNESS.sqrt();
+        let acceleration = (desired_offset - self.offset) * CAMERA_SPRING_STIFFNESS
+            - self.offset_velocity * damping;
+        self.offset_velocity += acceleration * delta_seconds;
+        self.offset += self.offset_velocity * delta_seconds;
+        self.offset
+    }
+
+    // Jumps straight to an offset, for modes that don't use the spring
+    fn snap_offset(&mut self, offset: Vec3) {
+        self.offset = offset;
+        self.offset_velocity = Vec3::zero();
+    }
+}
+
+// How far along the line from a focus to the camera it can go before something is in the way,
+// from 0 to 1
+fn unobstructed_fraction(focus: Vec3, camera_position: Vec3, obstacles: &[(Vec3, f32)]) -> f32 {
+    let path = camera_position - focus;
+    let length = path.length();
+    if length <= 0. {
+        return 1.;
+    }
+    let direction = path / length;
+
+    obstacles
+        .iter()
+        .filter_map(|(center, radius)| {
+            let radius = radius + CAMERA_COLLISION_MARGIN;
+            let to_center = *center - focus;
+
+            // Don't get stuck on whatever the focus is inside of
+            if to_center.length_squared() <= radius * radius {
+                return None;
+            }
+
+            // First intersection of the line with the sphere
+            let along = to_center.dot(direction);
+            let closest_squared = to_center.length_squared() - along * along;
+            if along < 0. || closest_squared > radius * radius {
+                return None;
+            }
+            let hit = along - (radius * radius - closest_squared).sqrt();
+            if hit > length {
+                return None;
+            }
+            Some(hit / length)
+        })
+        .fold(1., f32::min)
+}
+
+fn camera_mode_update(key_input: Res<Input<KeyCode>>, mut controller: ResMut<CameraController>) {
+    if !key_input.just_pressed(KeyCode::C) {
+        return;
+    }
+
+    let index = CAMERA_MODES
+        .iter()
+        .position(|mode| *mode == controller.mode)
+        .unwrap_or(0);
+    controller.mode = CAMERA_MODES[(index + 1) % CAMERA_MODES.len()];
+}
+
+fn camera_update(
+    time: Res<Time>,
+    key_input: Res<Input<KeyCode>>,
+    target_lock: Res<TargetLock>,
+    mut controller: ResMut<CameraController>,
+    mut camera_query: Query<(&CameraInput, &mut Transform)>,
+    player_query: Query<(&PlayerInput, &Transform), Without<CameraInput>>,
+    object_query: Query<(&Transform, &Collision), Without<CameraInput>>,
+) {
+    let delta_seconds = time.delta_seconds;
+    let rotation_blend = 1. - (-CAMERA_ROTATION_SHARPNESS * delta_seconds).exp();
+
+    let player = player_query
+        .iter()
+        .next()
+        .map(|(_, transform)| (transform.translation, transform.rotation));
+
+    // Only big things block the camera
+    let obstacles: Vec<(Vec3, f32)> = object_query
+        .iter()
+        .filter(|(_, c)| c.etype == EntityType::Asteroid || c.etype == EntityType::Planet)
+        .map(|(t, c)| (t.translation, c.radius))
+        .collect();
+
+    for (_, mut camera_transform) in camera_query.iter_mut() {
+        // The free camera takes over from wherever the camera was
+        if controller.mode != CameraMode::Free {
+            controller.free_position = controller.position;
+            controller.free_rotation = controller.rotation;
+        }
+
+        let (player_position, player_rotation) = match (controller.mode, player) {
+            (CameraMode::Free, _) | (_, None) => {
+                let forward = -(controller.free_rotation * Vec3::unit_z());
+                let right = controller.free_rotation * Vec3::unit_x();
+                let up = controller.free_rotation * Vec3::unit_y();
+
+                let mut movement = Vec3::zero();
+                if key_input.pressed(KeyCode::Up) {
+                    movement += forward;
+                }
+                if key_input.pressed(KeyCode::Down) {
+                    movement -= forward;
+                }
+                if key_input.pressed(KeyCode::Right) {
+                    movement += right;
+                }
+                if key_input.pressed(KeyCode::Left) {
+                    movement -= right;
+                }
+                if key_input.pressed(KeyCode::PageUp) {
+                    movement += up;
+                }
+                if key_input.pressed(KeyCode::PageDown) {
+                    movement -= up;
+                }
+                let boost = if key_input.pressed(KeyCode::RShift) {
+                    FREE_CAMERA_BOOST
+                } else {
+                    1.
+                };
+                controller.free_position += movement * FREE_CAMERA_SPEED * boost * delta_seconds;
+
+                controller.position = controller.free_position;
+                controller.rotation = controller.free_rotation;
+                apply_shake(&controller, &mut camera_transform);
+                continue;
+            }
+            (_, Some(player)) => player,
+        };
+
+        match controller.mode {
+            CameraMode::Chase => {
+                // The ship looks down the negative-z axis, so behind it is positive z
+                let desired_offset = player_rotation * Vec3::new(0., CHASE_HEIGHT, CHASE_DISTANCE);
+                let offset = controller.spring_offset(desired_offset, delta_seconds);
+
+                let desired_position = player_position + offset;
+                let fraction = unobstructed_fraction(player_position, desired_position, &obstacles);
+                controller.position = player_position + offset * fraction;
+                controller.rotation = controller
+                    .rotation
+                    .slerp(player_rotation, rotation_blend)
+                    .normalize();
+            }
+            CameraMode::Cockpit => {
+                let offset = player_rotation * Vec3::new(0., COCKPIT_HEIGHT, -COCKPIT_FORWARD);
+                controller.snap_offset(offset);
+                controller.position = player_position + offset;
+                controller.rotation = player_rotation;
+            }
+            CameraMode::Orbit => {
+                let focus = target_lock
+                    .target
+                    .and_then(|target| object_query.get(target).ok())
+                    .map(|(t, _)| t.translation)
+                    .unwrap_or(player_position);
+
+                controller.orbit_angle += ORBIT_RATE * delta_seconds;
+                let desired_offset = Quat::from_rotation_y(controller.orbit_angle)
+                    * Vec3::new(0., ORBIT_HEIGHT, ORBIT_DISTANCE);
+                let offset = controller.spring_offset(desired_offset, delta_seconds);
+
+                let desired_position = focus + offset;
+                let fraction = unobstructed_fraction(focus, desired_position, &obstacles);
+                let position = focus + offset * fraction;
+
+                let looking_at_focus =
+                    Transform::from_translation(position).looking_at(focus, Vec3::unit_y());
+                controller.position = position;
+                controller.rotation = controller
+                    .rotation
+                    .slerp(looking_at_focus.rotation, rotation_blend)
+                    .normalize();
+            }
+            CameraMode::Free => (),
+        }
+
+        apply_shake(&controller, &mut camera_transform);
+    }
+}
+
+fn apply_shake(controller: &CameraController, camera_transform: &mut Transform) {
+    camera_transform.translation =
+        controller.position + controller.rotation * controller.shake_offset;
+    camera_transform.rotation = (controller.rotation * controller.shake_rotation).normalize();
+}
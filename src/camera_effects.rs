@@ -0,0 +1,293 @@
+use bevy::prelude::*;
+use bevy::render::camera::PerspectiveProjection;
+
+use crate::args::parse_f32_flags;
+use crate::camera::CameraController;
+use crate::explosion::ExplosionEvent;
+use crate::input::{CameraInput, PlayerInput};
+use crate::noise::ValueNoise;
+use crate::velocity::*;
+
+pub struct CameraEffectsPlugin {
+    pub settings: CameraEffectsSettings,
+}
+
+impl Plugin for CameraEffectsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(self.settings)
+            .add_resource(CameraEffects::default())
+            .add_event::<CameraEffectEvent>()
+            .add_startup_system(setup_hit_flash)
+            .add_system(camera_effects_settings_update)
+            .add_system(camera_effect_events_update)
+            .add_system(camera_shake_update)
+            .add_system(camera_fov_update)
+            .add_system(hit_flash_update);
+    }
+}
+
+// Something happened that the camera should react to
+pub enum CameraEffectEvent {
+    // Adds trauma, from 0 to 1, which the camera shakes with
+    Shake(f32),
+    // Keeps trauma at least this high, sent every frame for a steady rumble like the thrusters
+    Rumble(f32),
+    // The player was hit, from 0 for a graze to 1 for a fatal blow
+    Hit(f32),
+}
+
+// Shake grows with the square of trauma, so small knocks are subtle and big ones violent
+pub const MAX_TRAUMA: f32 = 1.;
+// Trauma lost per second
+pub const TRAUMA_DECAY: f32 = 1.2;
+pub const MAX_SHAKE_OFFSET: f32 = 0.6;
+// Radians
+pub const MAX_SHAKE_ANGLE: f32 = 0.05;
+// How quickly the shake wobbles, in noise lattice cells per second
+pub const SHAKE_FREQUENCY: f32 = 15.;
+
+// Explosions closer to the camera than this shake it
+pub const EXPLOSION_SHAKE_RANGE: f32 = 80.;
+pub const EXPLOSION_TRAUMA: f32 = 0.6;
+pub const HIT_TRAUMA: f32 = 0.7;
+
+// The field of view widens between these speeds
+pub const FOV_KICK_MIN_SPEED: f32 = 20.;
+pub const FOV_KICK_MAX_SPEED: f32 = 80.;
+// Radians added to the field of view at full speed
+pub const MAX_FOV_KICK: f32 = 0.25;
+// How quickly the field of view follows the speed, higher is faster
+const FOV_SHARPNESS: f32 = 3.;
+
+pub const MAX_HIT_FLASH_ALPHA: f32 = 0.35;
+// Flash lost per second
+pub const HIT_FLASH_DECAY: f32 = 3.;
+
+// Scales for each effect, from 0 to turn it off. F9 turns them all off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraEffectsSettings {
+    pub enabled: bool,
+    pub shake_scale: f32,
+    pub fov_scale: f32,
+    pub flash_scale: f32,
+}
+
+impl Default for CameraEffectsSettings {
+    fn default() -> Self {
+        CameraEffectsSettings {
+            enabled: true,
+            shake_scale: 1.,
+            fov_scale: 1.,
+            flash_scale: 1.,
+        }
+    }
+}
+
+impl CameraEffectsSettings {
+    // -no-camera-effects turns them all off,
+    // and each can be scaled with e.g. -camera-shake=0.5
+    pub fn from_args(args: &[String]) -> CameraEffectsSettings {
+        let mut settings = CameraEffectsSettings::default();
+        settings.enabled = !args.contains(&String::from("-no-camera-effects"));
+
+        parse_f32_flags(
+            args,
+            &mut [
+                ("-camera-shake", &mut settings.shake_scale),
+                ("-camera-fov-kick", &mut settings.fov_scale),
+                ("-hit-flash", &mut settings.flash_scale),
+            ],
+        );
+
+        settings
+    }
+}
+
+pub struct CameraEffects {
+    pub trauma: f32,
+    pub flash: f32,
+    fov_kick: f32,
+    noise: ValueNoise,
+}
+
+impl Default for CameraEffects {
+    fn default() -> Self {
+        CameraEffects {
+            trauma: 0.,
+            flash: 0.,
+            fov_kick: 0.,
+            noise: ValueNoise::new(0),
+        }
+    }
+}
+
+impl CameraEffects {
+    fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma.max(0.)).min(MAX_TRAUMA);
+    }
+}
+
+struct HitFlash {
+    material: Handle<ColorMaterial>,
+}
+
+fn setup_hit_flash(commands: &mut Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+    let material = materials.add(Color::rgba(1.0, 0.1, 0.1, 0.).into());
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                display: Display::None,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            material: material.clone(),
+            ..Default::default()
+        })
+        .with(HitFlash { material });
+}
+
+fn camera_effects_settings_update(
+    key_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<CameraEffectsSettings>,
+    mut effects: ResMut<CameraEffects>,
+) {
+    if key_input.just_pressed(KeyCode::F9) {
+        settings.enabled = !settings.enabled;
+        effects.trauma = 0.;
+        effects.flash = 0.;
+    }
+}
+
+fn camera_effect_events_update(
+    settings: Res<CameraEffectsSettings>,
+    controller: Res<CameraController>,
+    mut effects: ResMut<CameraEffects>,
+    mut effect_event_reader: Local<EventReader<CameraEffectEvent>>,
+    effect_events: Res<Events<CameraEffectEvent>>,
+    mut explosion_event_reader: Local<EventReader<ExplosionEvent>>,
+    explosion_events: Res<Events<ExplosionEvent>>,
+) {
+    // Read the events even when disabled, so old ones don't all land when re-enabled
+    let effect_events: Vec<&CameraEffectEvent> = effect_event_reader.iter(&effect_events).collect();
+    let explosion_events: Vec<&ExplosionEvent> =
+        explosion_event_reader.iter(&explosion_events).collect();
+    if !settings.enabled {
+        return;
+    }
+
+    for event in effect_events {
+        match event {
+            CameraEffectEvent::Shake(trauma) => effects.add_trauma(*trauma),
+            CameraEffectEvent::Rumble(trauma) => {
+                effects.trauma = effects.trauma.max(trauma.min(MAX_TRAUMA));
+            }
+            CameraEffectEvent::Hit(severity) => {
+                let severity = severity.max(0.).min(1.);
+                effects.add_trauma(HIT_TRAUMA * severity);
+                effects.flash = effects.flash.max(severity);
+            }
+        }
+    }
+
+    for event in explosion_events {
+        let distance = (event.position - controller.position).length();
+        let falloff = 1. - distance / EXPLOSION_SHAKE_RANGE;
+        if falloff > 0. {
+            effects.add_trauma(EXPLOSION_TRAUMA * falloff);
+        }
+    }
+}
+
+fn camera_shake_update(
+    time: Res<Time>,
+    settings: Res<CameraEffectsSettings>,
+    mut effects: ResMut<CameraEffects>,
+    mut controller: ResMut<CameraController>,
+) {
+    effects.trauma = (effects.trauma - TRAUMA_DECAY * time.delta_seconds).max(0.);
+
+    let shake = if settings.enabled {
+        effects.trauma * effects.trauma * settings.shake_scale
+    } else {
+        0.
+    };
+    if shake <= 0. {
+        controller.shake_offset = Vec3::zero();
+        controller.shake_rotation = Quat::identity();
+        return;
+    }
+
+    // Each axis follows its own row of noise so they don't move together
+    let t = time.seconds_since_startup as f32 * SHAKE_FREQUENCY;
+    let noise = |row: f32| effects.noise.sample(Vec3::new(t, row * 7.3, 0.));
+
+    controller.shake_offset = Vec3::new(noise(0.), noise(1.), noise(2.)) * MAX_SHAKE_OFFSET * shake;
+    controller.shake_rotation = Quat::from_rotation_ypr(
+        noise(3.) * MAX_SHAKE_ANGLE * shake,
+        noise(4.) * MAX_SHAKE_ANGLE * shake,
+        noise(5.) * MAX_SHAKE_ANGLE * shake,
+    );
+}
+
+fn camera_fov_update(
+    time: Res<Time>,
+    settings: Res<CameraEffectsSettings>,
+    mut effects: ResMut<CameraEffects>,
+    player_query: Query<(&PlayerInput, &Velocity)>,
+    mut camera_query: Query<(&CameraInput, &mut PerspectiveProjection)>,
+) {
+    let speed = player_query
+        .iter()
+        .next()
+        .map(|(_, velocity)| velocity.velocity.length())
+        .unwrap_or(0.);
+
+    let desired_fov_kick = if settings.enabled {
+        let t = (speed - FOV_KICK_MIN_SPEED) / (FOV_KICK_MAX_SPEED - FOV_KICK_MIN_SPEED);
+        let t = t.max(0.).min(1.);
+        // Smoothstep so the kick eases in and out
+        t * t * (3. - 2. * t) * MAX_FOV_KICK * settings.fov_scale
+    } else {
+        0.
+    };
+
+    let blend = 1. - (-FOV_SHARPNESS * time.delta_seconds).exp();
+    effects.fov_kick += (desired_fov_kick - effects.fov_kick) * blend;
+
+    let default_fov = PerspectiveProjection::default().fov;
+    for (_, mut projection) in camera_query.iter_mut() {
+        let fov = default_fov + effects.fov_kick;
+        // Only touch the projection when it changes, so the camera isn't recalculated every frame
+        if (projection.fov - fov).abs() > 0.0001 {
+            projection.fov = fov;
+        }
+    }
+}
+
+fn hit_flash_update(
+    time: Res<Time>,
+    settings: Res<CameraEffectsSettings>,
+    mut effects: ResMut<CameraEffects>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut flash_query: Query<(&HitFlash, &mut Style)>,
+) {
+    effects.flash = (effects.flash - HIT_FLASH_DECAY * time.delta_seconds).max(0.);
+
+    let alpha = if settings.enabled {
+        (effects.flash * MAX_HIT_FLASH_ALPHA * settings.flash_scale).min(1.)
+    } else {
+        0.
+    };
+
+    for (flash, mut style) in flash_query.iter_mut() {
+        if alpha <= 0. {
+            style.display = Display::None;
+            continue;
+        }
+        style.display = Display::Flex;
+        if let Some(material) = materials.get_mut(&flash.material) {
+            material.color.set_a(alpha);
+        }
+    }
+}
@@ -1,9 +1,14 @@
 use bevy::prelude::*;
 
+use crate::asteroids::{AsteroidDamageEvent, AsteroidImpactEvent, ASTEROID_IMPACT_MIN_ENERGY};
 use crate::audio::*;
-use crate::cooldown::*;
+use crate::bullets::BulletSpawner;
+use crate::camera_effects::CameraEffectEvent;
+use crate::difficulty::Difficulty;
 use crate::velocity::*;
 use crate::explosion::ExplosionEvent;
+use crate::particles::{ParticleKind, Particles};
+use crate::planets::{PlanetContactEvent, PlanetImpactEvent};
 pub use crate::EntityType;
 use crate::GameState;
 
@@ -16,6 +21,7 @@ pub struct Collision {
 
 const COLLISION_SPRING_CONSTANT: f32 = 2048.;
 
+#[derive(Clone, Copy)]
 struct CollisionData {
     entity: Entity,
     position: Vec3, // Position
@@ -23,7 +29,75 @@ struct CollisionData {
     collision: Collision,
 }
 
-const ASTEROID_COLLISION_SOUND_DURATION: f64 = 1.;
+impl CollisionData {
+    // Where the object was at the given time since the start of the frame,
+    // assuming velocity_update has already moved it to the end of the frame
+    fn at_time(&self, time_since_frame_start: f32, frame_duration: f32) -> CollisionData {
+        CollisionData {
+            position: self.position - self.velocity * (frame_duration - time_since_frame_start),
+            ..*self
+        }
+    }
+}
+
+// Swept-sphere test for bodies that move further than their size in a single frame.
+// Takes the displacement between the spheres at the end of the frame and their relative
+// velocity, and returns the time since the start of the frame that they first touched
+pub fn swept_sphere_time_of_impact(
+    displacement: Vec3,
+    relative_velocity: Vec3,
+    combined_radius: f32,
+    frame_duration: f32,
+) -> Option<f32> {
+    // Displacement at the start of the frame
+    let start_displacement = displacement - relative_velocity * frame_duration;
+
+    // Solve |start_displacement + relative_velocity * t| = combined_radius for t
+    let a = relative_velocity.length_squared();
+    let b = 2. * start_displacement.dot(relative_velocity);
+    let c = start_displacement.length_squared() - combined_radius * combined_radius;
+
+    if c <= 0. {
+        // Already touching at the start of the frame, which only counts if they were
+        // still closing in rather than bouncing apart
+        if b < 0. {
+            return Some(0.);
+        }
+        return None;
+    }
+    if a <= 0. {
+        return None;
+    }
+
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return None;
+    }
+
+    let time_of_impact = (-b - discriminant.sqrt()) / (2. * a);
+    if time_of_impact < 0. || time_of_impact > frame_duration {
+        return None;
+    }
+    Some(time_of_impact)
+}
+
+// When two bodies that don't overlap at the end of the frame touched during it.
+// Bodies moving less than the smaller one's radius a frame can't pass through each
+// other, so they are left to the overlap test.
+fn swept_collision(obj1: &CollisionData, obj2: &CollisionData, frame_duration: f32) -> Option<f32> {
+    let relative_velocity = obj2.velocity - obj1.velocity;
+    let frame_travel = relative_velocity.length() * frame_duration;
+    if frame_travel < obj1.collision.radius.min(obj2.collision.radius) {
+        return None;
+    }
+
+    swept_sphere_time_of_impact(
+        obj2.position - obj1.position,
+        relative_velocity,
+        obj1.collision.radius + obj2.collision.radius,
+        frame_duration,
+    )
+}
 
 pub fn collision_update(
     commands: &mut Commands,
@@ -31,14 +105,21 @@ pub fn collision_update(
     time: Res<Time>,
 
     mut game_state: ResMut<GameState>,
+    difficulty: Res<Difficulty>,
+    mut bullet_spawner: ResMut<BulletSpawner>,
 
     // For collision sound effects
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
-    mut collision_sound_cooldown: Local<Cooldown>,
+    sound_bank: Res<SoundBank>,
+    audio: Res<SpatialAudio>,
 
     mut query: Query<(Entity, &Transform, &mut Velocity, &Collision)>,
     mut expl_events: ResMut<Events<ExplosionEvent>>,
+    mut asteroid_damage_events: ResMut<Events<AsteroidDamageEvent>>,
+    mut asteroid_impact_events: ResMut<Events<AsteroidImpactEvent>>,
+    mut planet_impact_events: ResMut<Events<PlanetImpactEvent>>,
+    mut planet_contact_events: ResMut<Events<PlanetContactEvent>>,
+    mut camera_effect_events: ResMut<Events<CameraEffectEvent>>,
+    mut particles: ResMut<Particles>,
 ) {
     if *game_state != GameState::Running {
         return;
@@ -69,7 +150,31 @@ pub fn collision_update(
 
             let distance_squared = displacement.length_squared();
             if distance_squared > combined_radius_squared {
-                // No collision
+                // Fast bodies can pass straight through each other between frames,
+                // so check along the path they took this frame
+                let time_of_impact = swept_collision(obj1, obj2, time.delta_seconds);
+                if let Some(time_of_impact) = time_of_impact {
+                    // They have passed through each other, so there is nothing
+                    // to push apart, but the hit still counts
+                    collision_gameplay_logic(
+                        commands,
+                        &*time,
+                        &mut game_state,
+                        &difficulty,
+                        &mut bullet_spawner,
+                        &sound_bank,
+                        &audio,
+                        &obj1.at_time(time_of_impact, time.delta_seconds),
+                        &obj2.at_time(time_of_impact, time.delta_seconds),
+                        &mut expl_events,
+                        &mut asteroid_damage_events,
+                        &mut asteroid_impact_events,
+                        &mut planet_impact_events,
+                        &mut planet_contact_events,
+                        &mut camera_effect_events,
+                        &mut particles,
+                    );
+                }
                 continue;
             }
 
@@ -77,12 +182,19 @@ pub fn collision_update(
                 commands,
                 &*time,
                 &mut game_state,
-                &asset_server,
+                &difficulty,
+                &mut bullet_spawner,
+                &sound_bank,
                 &audio,
-                &mut collision_sound_cooldown,
                 obj1,
                 obj2,
-                &mut expl_events
+                &mut expl_events,
+                &mut asteroid_damage_events,
+                &mut asteroid_impact_events,
+                &mut planet_impact_events,
+                &mut planet_contact_events,
+                &mut camera_effect_events,
+                &mut particles,
             );
 
             let distance = distance_squared.sqrt();
@@ -114,13 +226,81 @@ pub fn collision_update(
     }
 }
 
-const LETHAL_RELATIVE_VELOCITY_OF_ASTEROID: f32 = 3.;
-const LETHAL_RELATIVE_VELOCITY_OF_ASTEROID_SQUARED: f32 =
-    LETHAL_RELATIVE_VELOCITY_OF_ASTEROID * LETHAL_RELATIVE_VELOCITY_OF_ASTEROID;
+// Bullets and mines go back to the bullet pool rather than being despawned
+fn despawn_projectile(
+    commands: &mut Commands,
+    bullet_spawner: &mut BulletSpawner,
+    time: &Time,
+    obj: &CollisionData,
+) {
+    match obj.collision.etype {
+        EntityType::Bullet | EntityType::Mine => bullet_spawner.despawn(commands, time, obj.entity),
+        _ => commands.despawn(obj.entity),
+    }
+}
+
+// How hard the player was hit, as a fraction of the speed that would have destroyed them
+pub fn hit_severity(relative_speed_squared: f32, lethal_speed_squared: f32) -> f32 {
+    if lethal_speed_squared <= 0. {
+        return 1.;
+    }
+    (relative_speed_squared / lethal_speed_squared)
+        .sqrt()
+        .min(1.)
+}
+
+// Kinetic energy of the impact in the centre of mass frame
+fn impact_energy(obj1: &CollisionData, obj2: &CollisionData) -> f32 {
+    let m1 = obj1.collision.mass;
+    let m2 = obj2.collision.mass;
+    let reduced_mass = m1 * m2 / (m1 + m2);
+    let relative_velocity = obj1.velocity - obj2.velocity;
+    0.5 * reduced_mass * relative_velocity.length_squared()
+}
+
+// Whether the objects are moving towards each other, rather than already bouncing apart
+fn closing(obj1: &CollisionData, obj2: &CollisionData) -> bool {
+    let displacement = obj2.position - obj1.position;
+    let relative_velocity = obj2.velocity - obj1.velocity;
+    displacement.dot(relative_velocity) < 0.
+}
+
+// Sparks fly back the way the projectile came, off whatever it hit
+fn projectile_impact(
+    particles: &mut Particles,
+    target: &CollisionData,
+    projectile: &CollisionData,
+) {
+    particles.burst(
+        ParticleKind::Impact,
+        projectile.position,
+        target.velocity,
+        target.velocity - projectile.velocity,
+        1.,
+    );
+}
 
+// Dust knocked off an asteroid where something touches it
+fn asteroid_dust(particles: &mut Particles, asteroid: &CollisionData, other: &CollisionData) {
+    let displacement = other.position - asteroid.position;
+    let distance = displacement.length();
+    let contact_point = if distance > 0. {
+        asteroid.position + displacement / distance * asteroid.collision.radius.min(distance)
+    } else {
+        asteroid.position
+    };
+    particles.burst(
+        ParticleKind::Dust,
+        contact_point,
+        asteroid.velocity,
+        displacement,
+        1.,
+    );
+}
+
+// Scaled by the difficulty's lethal_speed_scale
+pub const LETHAL_RELATIVE_VELOCITY_OF_ASTEROID: f32 = 3.;
 const LETHAL_RELATIVE_VELOCITY_OF_BULLET: f32 = 10.;
-const LETHAL_RELATIVE_VELOCITY_OF_BULLET_SQUARED: f32 =
-    LETHAL_RELATIVE_VELOCITY_OF_BULLET * LETHAL_RELATIVE_VELOCITY_OF_BULLET;
 
 // Objects parameters to collision_gameplay_logic are ordered by collision type
 // to reduce the number of permutations
@@ -129,16 +309,23 @@ fn collision_gameplay_logic(
     time: &Time,
 
     game_state: &mut GameState,
+    difficulty: &Difficulty,
+    bullet_spawner: &mut ResMut<BulletSpawner>,
 
     // For collision sound effects
-    asset_server: &Res<AssetServer>,
-    audio: &Res<Audio>,
-    collision_sound_cooldown: &mut Local<Cooldown>,
+    sound_bank: &Res<SoundBank>,
+    audio: &Res<SpatialAudio>,
 
     obj_a: &CollisionData,
     obj_b: &CollisionData,
 
     expl_events: &mut ResMut<Events<ExplosionEvent>>,
+    asteroid_damage_events: &mut ResMut<Events<AsteroidDamageEvent>>,
+    asteroid_impact_events: &mut ResMut<Events<AsteroidImpactEvent>>,
+    planet_impact_events: &mut ResMut<Events<PlanetImpactEvent>>,
+    planet_contact_events: &mut ResMut<Events<PlanetContactEvent>>,
+    camera_effect_events: &mut ResMut<Events<CameraEffectEvent>>,
+    particles: &mut ResMut<Particles>,
 ) {
     // Order the objects by collision type to reduce the number of permutations
     let obj1 = if obj_a.collision.etype <= obj_b.collision.etype {
@@ -152,92 +339,296 @@ fn collision_gameplay_logic(
         &obj_a
     };
 
+    let lethal_speed_of_asteroid_squared =
+        difficulty.lethal_speed_squared(LETHAL_RELATIVE_VELOCITY_OF_ASTEROID);
+    let lethal_speed_of_bullet_squared =
+        difficulty.lethal_speed_squared(LETHAL_RELATIVE_VELOCITY_OF_BULLET);
+
     // NOTE: Definitely could be less duplication in this collision code,
     // but it's a game jam and there many more features to implement
 
     match (obj1.collision.etype, obj2.collision.etype) {
-        (EntityType::Asteroid, EntityType::Asteroid) => (),
-        (EntityType::Asteroid, EntityType::Earth) => (),
+        (EntityType::Asteroid, EntityType::Asteroid) => {
+            let energy = impact_energy(obj1, obj2);
+            if closing(obj1, obj2) && energy > ASTEROID_IMPACT_MIN_ENERGY {
+                asteroid_impact_events.send(AsteroidImpactEvent {
+                    asteroid_a: obj1.entity,
+                    asteroid_b: obj2.entity,
+                    energy,
+                });
+            }
+        }
+        (EntityType::Asteroid, EntityType::Planet) => {
+            if closing(obj1, obj2) {
+                planet_impact_events.send(PlanetImpactEvent {
+                    planet: obj2.entity,
+                    asteroid: obj1.entity,
+                    energy: impact_energy(obj1, obj2),
+                    position: obj1.position,
+                    velocity: obj2.velocity,
+                });
+            }
+        }
         (EntityType::Asteroid, EntityType::Alien) => {
             let relative_velocity = obj1.velocity - obj2.velocity;
             let relative_speed_squared = relative_velocity.length_squared();
 
-            if relative_speed_squared > LETHAL_RELATIVE_VELOCITY_OF_ASTEROID_SQUARED {
-                play_sound(asset_server, audio, "audio/EnemyExplode.mp3");
+            if relative_speed_squared > lethal_speed_of_asteroid_squared {
+                play_sound_at(
+                    sound_bank,
+                    audio,
+                    Sound::EnemyExplode,
+                    obj2.position,
+                    obj2.velocity,
+                );
+                asteroid_damage_events.send(AsteroidDamageEvent {
+                    entity: obj1.entity,
+                    damage: impact_energy(obj1, obj2),
+                });
                 commands.despawn(obj2.entity);
                 expl_events.send(ExplosionEvent::new(obj2.position, obj2.velocity));
-            } else if collision_sound_cooldown.over(&time) {
-                play_sound(asset_server, audio, "audio/AsteroidCollision.mp3");
-                collision_sound_cooldown.reset(&time, ASTEROID_COLLISION_SOUND_DURATION);
+            } else {
+                play_sound_at(
+                    sound_bank,
+                    audio,
+                    Sound::AsteroidCollision,
+                    obj2.position,
+                    obj2.velocity,
+                );
+                if closing(obj1, obj2) {
+                    asteroid_dust(particles, obj1, obj2);
+                }
             }
         }
         (EntityType::Asteroid, EntityType::Player) => {
             let relative_velocity = obj1.velocity - obj2.velocity;
             let relative_speed_squared = relative_velocity.length_squared();
 
-            if relative_speed_squared > LETHAL_RELATIVE_VELOCITY_OF_ASTEROID_SQUARED {
-                play_sound(asset_server, audio, "audio/SpaceshipCrash2.mp3");
+            if closing(obj1, obj2) {
+                camera_effect_events.send(CameraEffectEvent::Hit(hit_severity(
+                    relative_speed_squared,
+                    lethal_speed_of_asteroid_squared,
+                )));
+            }
+
+            if relative_speed_squared > lethal_speed_of_asteroid_squared {
+                play_sound_at(
+                    sound_bank,
+                    audio,
+                    Sound::PlayerCrash,
+                    obj2.position,
+                    obj2.velocity,
+                );
+                asteroid_damage_events.send(AsteroidDamageEvent {
+                    entity: obj1.entity,
+                    damage: impact_energy(obj1, obj2),
+                });
                 commands.despawn(obj2.entity);
                 expl_events.send(ExplosionEvent::new(obj2.position, obj2.velocity));
                 *game_state = GameState::Lost;
-            } else if collision_sound_cooldown.over(&time) {
-                play_sound(asset_server, audio, "audio/AsteroidCollision.mp3");
-                collision_sound_cooldown.reset(&time, ASTEROID_COLLISION_SOUND_DURATION);
+            } else {
+                play_sound_at(
+                    sound_bank,
+                    audio,
+                    Sound::AsteroidCollision,
+                    obj2.position,
+                    obj2.velocity,
+                );
+                if closing(obj1, obj2) {
+                    asteroid_dust(particles, obj1, obj2);
+                }
             }
         }
-        (EntityType::Earth, EntityType::Player) => {
-            if *game_state != GameState::Running {
-                return;
-            }
-
-            play_sound(asset_server, audio, "audio/GameWin.mp3");
-            println!("YOU WIN!!");
-            *game_state = GameState::Won;
+        (EntityType::Planet, EntityType::Player) => {
+            // What touching a planet does depends on the planet
+            planet_contact_events.send(PlanetContactEvent {
+                planet: obj1.entity,
+                ship: obj2.entity,
+                position: obj2.position,
+                velocity: obj2.velocity,
+            });
         }
         (EntityType::Bullet, EntityType::Alien) => {
             let relative_velocity = obj1.velocity - obj2.velocity;
             let relative_speed_squared = relative_velocity.length_squared();
-
-            if relative_speed_squared > LETHAL_RELATIVE_VELOCITY_OF_BULLET_SQUARED {
-                play_sound(asset_server, audio, "audio/EnemyExplode.mp3");
-                commands.despawn(obj1.entity);
+            projectile_impact(particles, obj2, obj1);
+
+            if relative_speed_squared > lethal_speed_of_bullet_squared {
+                play_sound_at(
+                    sound_bank,
+                    audio,
+                    Sound::EnemyExplode,
+                    obj2.position,
+                    obj2.velocity,
+                );
+                despawn_projectile(commands, bullet_spawner, time, obj1);
                 commands.despawn(obj2.entity);
                 expl_events.send(ExplosionEvent::new(obj2.position, obj2.velocity));
-            } else if collision_sound_cooldown.over(&time) {
-                play_sound(asset_server, audio, "audio/AsteroidCollision.mp3");
-                collision_sound_cooldown.reset(&time, ASTEROID_COLLISION_SOUND_DURATION);
+            } else {
+                play_sound_at(
+                    sound_bank,
+                    audio,
+                    Sound::AsteroidCollision,
+                    obj2.position,
+                    obj2.velocity,
+                );
             }
         }
         (EntityType::Bullet, EntityType::Player) => {
             let relative_velocity = obj1.velocity - obj2.velocity;
             let relative_speed_squared = relative_velocity.length_squared();
+            projectile_impact(particles, obj2, obj1);
 
-            if relative_speed_squared > LETHAL_RELATIVE_VELOCITY_OF_BULLET_SQUARED {
-                play_sound(asset_server, audio, "audio/SpaceshipCrash2.mp3");
-                commands.despawn(obj1.entity);
+            if closing(obj1, obj2) {
+                camera_effect_events.send(CameraEffectEvent::Hit(hit_severity(
+                    relative_speed_squared,
+                    lethal_speed_of_bullet_squared,
+                )));
+            }
+
+            if relative_speed_squared > lethal_speed_of_bullet_squared {
+                play_sound_at(
+                    sound_bank,
+                    audio,
+                    Sound::PlayerCrash,
+                    obj2.position,
+                    obj2.velocity,
+                );
+                despawn_projectile(commands, bullet_spawner, time, obj1);
                 commands.despawn(obj2.entity);
                 expl_events.send(ExplosionEvent::new(obj2.position, obj2.velocity));
                 *game_state = GameState::Lost;
-            } else if collision_sound_cooldown.over(&time) {
-                play_sound(asset_server, audio, "audio/AsteroidCollision.mp3");
-                collision_sound_cooldown.reset(&time, ASTEROID_COLLISION_SOUND_DURATION);
+            } else {
+                play_sound_at(
+                    sound_bank,
+                    audio,
+                    Sound::AsteroidCollision,
+                    obj2.position,
+                    obj2.velocity,
+                );
             }
         }
+        (EntityType::Asteroid, EntityType::Bullet) => {
+            asteroid_damage_events.send(AsteroidDamageEvent {
+                entity: obj1.entity,
+                damage: impact_energy(obj1, obj2),
+            });
+            projectile_impact(particles, obj1, obj2);
+            asteroid_dust(particles, obj1, obj2);
+            despawn_projectile(commands, bullet_spawner, time, obj2);
+        }
+        (EntityType::Asteroid, EntityType::Mine)
+        | (EntityType::Planet, EntityType::Bullet)
+        | (EntityType::Planet, EntityType::Mine) => {
+            projectile_impact(particles, obj1, obj2);
+            despawn_projectile(commands, bullet_spawner, time, obj2);
+        }
+        (EntityType::Asteroid, EntityType::Missile) => {
+            asteroid_damage_events.send(AsteroidDamageEvent {
+                entity: obj1.entity,
+                damage: impact_energy(obj1, obj2),
+            });
+            commands.despawn(obj2.entity);
+            expl_events.send(ExplosionEvent::warhead(obj2.position, obj2.velocity));
+        }
+        (EntityType::Planet, EntityType::Missile) => {
+            commands.despawn(obj2.entity);
+            expl_events.send(ExplosionEvent::warhead(obj2.position, obj2.velocity));
+        }
+        (EntityType::Bullet, EntityType::Missile)
+        | (EntityType::Mine, EntityType::Missile)
+        | (EntityType::Missile, EntityType::Missile) => {
+            // Missiles can be shot down
+            play_sound_at(
+                sound_bank,
+                audio,
+                Sound::EnemyExplode,
+                obj2.position,
+                obj2.velocity,
+            );
+            despawn_projectile(commands, bullet_spawner, time, obj1);
+            commands.despawn(obj2.entity);
+            expl_events.send(ExplosionEvent::warhead(obj2.position, obj2.velocity));
+        }
+        (EntityType::Missile, EntityType::Alien) => {
+            play_sound_at(
+                sound_bank,
+                audio,
+                Sound::EnemyExplode,
+                obj2.position,
+                obj2.velocity,
+            );
+            commands.despawn(obj1.entity);
+            commands.despawn(obj2.entity);
+            expl_events.send(ExplosionEvent::warhead(obj2.position, obj2.velocity));
+        }
+        (EntityType::Missile, EntityType::Player) => {
+            play_sound_at(
+                sound_bank,
+                audio,
+                Sound::PlayerCrash,
+                obj2.position,
+                obj2.velocity,
+            );
+            camera_effect_events.send(CameraEffectEvent::Hit(1.));
+            commands.despawn(obj1.entity);
+            commands.despawn(obj2.entity);
+            expl_events.send(ExplosionEvent::warhead(obj2.position, obj2.velocity));
+            *game_state = GameState::Lost;
+        }
+        (EntityType::Mine, EntityType::Alien) => {
+            // Mines go off on contact no matter how slowly the alien drifts into them
+            play_sound_at(
+                sound_bank,
+                audio,
+                Sound::EnemyExplode,
+                obj2.position,
+                obj2.velocity,
+            );
+            despawn_projectile(commands, bullet_spawner, time, obj1);
+            commands.despawn(obj2.entity);
+            expl_events.send(ExplosionEvent::warhead(obj1.position, obj1.velocity));
+            expl_events.send(ExplosionEvent::new(obj2.position, obj2.velocity));
+        }
         (EntityType::Alien, EntityType::Player) => {
             let relative_velocity = obj1.velocity - obj2.velocity;
             let relative_speed_squared = relative_velocity.length_squared();
 
-            if relative_speed_squared > LETHAL_RELATIVE_VELOCITY_OF_ASTEROID_SQUARED {
-                play_sound(asset_server, audio, "audio/SpaceshipCrash2.mp3");
-                play_sound(asset_server, audio, "audio/EnemyExplode.mp3");
+            if closing(obj1, obj2) {
+                camera_effect_events.send(CameraEffectEvent::Hit(hit_severity(
+                    relative_speed_squared,
+                    lethal_speed_of_asteroid_squared,
+                )));
+            }
+
+            if relative_speed_squared > lethal_speed_of_asteroid_squared {
+                play_sound_at(
+                    sound_bank,
+                    audio,
+                    Sound::PlayerCrash,
+                    obj2.position,
+                    obj2.velocity,
+                );
+                play_sound_at(
+                    sound_bank,
+                    audio,
+                    Sound::EnemyExplode,
+                    obj2.position,
+                    obj2.velocity,
+                );
                 commands.despawn(obj1.entity);
                 commands.despawn(obj2.entity);
                 expl_events.send(ExplosionEvent::new(obj1.position, obj1.velocity));
                 expl_events.send(ExplosionEvent::new(obj2.position, obj2.velocity));
                 *game_state = GameState::Lost;
-            } else if collision_sound_cooldown.over(&time) {
-                play_sound(asset_server, audio, "audio/AsteroidCollision.mp3");
-                collision_sound_cooldown.reset(&time, ASTEROID_COLLISION_SOUND_DURATION);
+            } else {
+                play_sound_at(
+                    sound_bank,
+                    audio,
+                    Sound::AsteroidCollision,
+                    obj2.position,
+                    obj2.velocity,
+                );
             }
         }
 
@@ -245,3 +636,191 @@ fn collision_gameplay_logic(
         _ => (),
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_DURATION_AT_10_FPS: f32 = 0.1;
+
+    // A bullet flying along the x-axis past an alien at the origin
+    const BULLET_SPEED: f32 = crate::bullets::RELATIVE_BULLET_SPEED;
+    const COMBINED_RADIUS: f32 = crate::bullets::BULLET_RADIUS + 2.;
+
+    fn overlapping(displacement: Vec3) -> bool {
+        displacement.length() <= COMBINED_RADIUS
+    }
+
+    #[test]
+    fn bullet_tunnelling_through_alien_is_hit() {
+        // 5 units in front of the alien at the start of the frame, 7 units past it at the end
+        let displacement = Vec3::new(7., 0., 0.);
+        let relative_velocity = Vec3::new(BULLET_SPEED, 0., 0.);
+
+        assert!(!overlapping(displacement));
+
+        let time_of_impact = swept_sphere_time_of_impact(
+            displacement,
+            relative_velocity,
+            COMBINED_RADIUS,
+            FRAME_DURATION_AT_10_FPS,
+        )
+        .expect("bullet should hit the alien");
+
+        let expected_time_of_impact = (5. - COMBINED_RADIUS) / BULLET_SPEED;
+        assert!((time_of_impact - expected_time_of_impact).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bullet_hits_alien_every_time_at_10_fps() {
+        let bullet_velocity = Vec3::new(BULLET_SPEED, 0., 0.);
+        let mut overlap_test_tunnelled = false;
+
+        // Try a range of starting offsets so the alien lands at different
+        // points between frames
+        for offset in 0..20 {
+            let mut bullet_position = Vec3::new(-50. - offset as f32 * 0.6, 0.5, 0.);
+
+            let mut swept_hit = false;
+            let mut overlap_hit = false;
+            while bullet_position.x < 50. {
+                bullet_position += bullet_velocity * FRAME_DURATION_AT_10_FPS;
+
+                overlap_hit |= overlapping(bullet_position);
+                swept_hit |= swept_sphere_time_of_impact(
+                    bullet_position,
+                    bullet_velocity,
+                    COMBINED_RADIUS,
+                    FRAME_DURATION_AT_10_FPS,
+                )
+                .is_some();
+            }
+
+            assert!(
+                swept_hit,
+                "bullet tunnelled through the alien at offset {}",
+                offset
+            );
+            overlap_test_tunnelled |= !overlap_hit;
+        }
+
+        // Otherwise this test proves nothing
+        assert!(overlap_test_tunnelled);
+    }
+
+    #[test]
+    fn bullet_passing_wide_of_alien_misses() {
+        let displacement = Vec3::new(7., COMBINED_RADIUS + 0.1, 0.);
+        let relative_velocity = Vec3::new(BULLET_SPEED, 0., 0.);
+
+        let time_of_impact = swept_sphere_time_of_impact(
+            displacement,
+            relative_velocity,
+            COMBINED_RADIUS,
+            FRAME_DURATION_AT_10_FPS,
+        );
+        assert_eq!(time_of_impact, None);
+    }
+
+    #[test]
+    fn bullet_short_of_alien_misses() {
+        // Still 10 units away at the end of the frame
+        let displacement = Vec3::new(-10., 0., 0.);
+        let relative_velocity = Vec3::new(BULLET_SPEED, 0., 0.);
+
+        let time_of_impact = swept_sphere_time_of_impact(
+            displacement,
+            relative_velocity,
+            COMBINED_RADIUS,
+            FRAME_DURATION_AT_10_FPS,
+        );
+        assert_eq!(time_of_impact, None);
+    }
+
+    #[test]
+    fn spheres_touching_at_start_and_moving_apart_miss() {
+        // Touching at the start of the frame, then 12 units apart at the end
+        let displacement = Vec3::new(COMBINED_RADIUS + 12., 0., 0.);
+        let relative_velocity = Vec3::new(BULLET_SPEED, 0., 0.);
+
+        let time_of_impact = swept_sphere_time_of_impact(
+            displacement,
+            relative_velocity,
+            COMBINED_RADIUS,
+            FRAME_DURATION_AT_10_FPS,
+        );
+        assert_eq!(time_of_impact, None);
+    }
+
+    fn collision_data(
+        etype: EntityType,
+        radius: f32,
+        position: Vec3,
+        velocity: Vec3,
+    ) -> CollisionData {
+        CollisionData {
+            entity: Entity::new(0),
+            position,
+            velocity,
+            collision: Collision {
+                mass: 1.,
+                radius,
+                etype,
+            },
+        }
+    }
+
+    #[test]
+    fn bullet_passes_the_swept_test_gate_at_10_fps() {
+        let alien = collision_data(EntityType::Alien, 2., Vec3::zero(), Vec3::zero());
+        let bullet_velocity = Vec3::new(BULLET_SPEED, 0., 0.);
+
+        for offset in 0..20 {
+            let mut bullet_position = Vec3::new(-50. - offset as f32 * 0.6, 0.5, 0.);
+            let mut hit = false;
+            while bullet_position.x < 50. {
+                bullet_position += bullet_velocity * FRAME_DURATION_AT_10_FPS;
+                let bullet = collision_data(
+                    EntityType::Bullet,
+                    crate::bullets::BULLET_RADIUS,
+                    bullet_position,
+                    bullet_velocity,
+                );
+                hit |= overlapping(bullet_position)
+                    || swept_collision(&alien, &bullet, FRAME_DURATION_AT_10_FPS).is_some();
+            }
+            assert!(
+                hit,
+                "bullet tunnelled through the alien at offset {}",
+                offset
+            );
+        }
+    }
+
+    #[test]
+    fn slow_bodies_are_left_to_the_overlap_test() {
+        // An alien drifting along the edge of an asteroid, just grazing it mid-frame
+        let asteroid = collision_data(EntityType::Asteroid, 3., Vec3::zero(), Vec3::zero());
+        let alien_velocity = Vec3::new(1., 0., 0.);
+        let alien = collision_data(
+            EntityType::Alien,
+            2.,
+            Vec3::new(0.05, 4.9998, 0.),
+            alien_velocity,
+        );
+
+        // The sweep would catch the graze, but the alien moves far less than its own
+        // size, so the swept test isn't run
+        assert!(swept_sphere_time_of_impact(
+            alien.position,
+            alien_velocity,
+            5.,
+            FRAME_DURATION_AT_10_FPS
+        )
+        .is_some());
+        assert_eq!(
+            swept_collision(&asteroid, &alien, FRAME_DURATION_AT_10_FPS),
+            None
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+
+// Game data files, alongside the rest of the assets
+pub const DATA_DIRECTORY: &str = "assets/data";
+
+// Found the way Bevy finds the assets folder: next to Cargo.toml when run through cargo,
+// otherwise next to the executable, so the game doesn't depend on where it's started from
+fn data_root() -> PathBuf {
+    if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+        return PathBuf::from(manifest_dir);
+    }
+    env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|directory| directory.to_path_buf()))
+        .unwrap_or_default()
+}
+
+pub fn data_path(file_name: &str) -> PathBuf {
+    data_root().join(DATA_DIRECTORY).join(file_name)
+}
+
+// Reads and parses a RON file from the data directory
+pub fn load_data<T: DeserializeOwned>(file_name: &str) -> Result<T, String> {
+    let path = data_path(file_name);
+    let data = fs::read_to_string(&path)
+        .map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+    ron::de::from_str(&data).map_err(|e| format!("Couldn't parse {}: {}", path.display(), e))
+}
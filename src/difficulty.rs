@@ -0,0 +1,148 @@
+use bevy::prelude::*;
+
+use crate::args::parse_f32_flags;
+use crate::GameState;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DifficultyPreset {
+    Easy,
+    Normal,
+    Hard,
+    Custom,
+}
+
+// Multipliers applied on top of the gameplay constants
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Difficulty {
+    pub preset: DifficultyPreset,
+
+    // Shots per second relative to ENEMY_WEAPON_COOLDOWN_DURATION
+    pub alien_fire_rate: f32,
+    // 1 is a perfect shot at the player, 0 is a wild guess
+    pub alien_accuracy: f32,
+    // Scales the relative velocities that destroy ships, higher is more forgiving
+    pub lethal_speed_scale: f32,
+    pub enemy_count_scale: f32,
+    pub gravity_scale: f32,
+}
+
+impl Difficulty {
+    pub fn from_preset(preset: DifficultyPreset) -> Difficulty {
+        match preset {
+            DifficultyPreset::Easy => Difficulty {
+                preset,
+                alien_fire_rate: 0.6,
+                alien_accuracy: 0.6,
+                lethal_speed_scale: 1.5,
+                enemy_count_scale: 0.6,
+                gravity_scale: 0.75,
+            },
+            DifficultyPreset::Normal | DifficultyPreset::Custom => Difficulty {
+                preset,
+                alien_fire_rate: 1.,
+                alien_accuracy: 0.9,
+                lethal_speed_scale: 1.,
+                enemy_count_scale: 1.,
+                gravity_scale: 1.,
+            },
+            DifficultyPreset::Hard => Difficulty {
+                preset,
+                alien_fire_rate: 1.5,
+                alien_accuracy: 1.,
+                lethal_speed_scale: 0.75,
+                enemy_count_scale: 1.5,
+                gravity_scale: 1.25,
+            },
+        }
+    }
+
+    // Presets are picked with -easy, -normal or -hard.
+    // Individual values can be overridden with e.g. -alien-fire-rate=2,
+    // which makes the difficulty Custom
+    pub fn from_args(args: &[String]) -> Difficulty {
+        let preset = if args.contains(&String::from("-easy")) {
+            DifficultyPreset::Easy
+        } else if args.contains(&String::from("-hard")) {
+            DifficultyPreset::Hard
+        } else {
+            DifficultyPreset::Normal
+        };
+
+        let mut difficulty = Difficulty::from_preset(preset);
+
+        let custom = parse_f32_flags(
+            args,
+            &mut [
+                ("-alien-fire-rate", &mut difficulty.alien_fire_rate),
+                ("-alien-accuracy", &mut difficulty.alien_accuracy),
+                ("-lethal-speed", &mut difficulty.lethal_speed_scale),
+                ("-enemy-count", &mut difficulty.enemy_count_scale),
+                ("-gravity", &mut difficulty.gravity_scale),
+            ],
+        );
+        if custom {
+            difficulty.preset = DifficultyPreset::Custom;
+        }
+
+        difficulty.alien_accuracy = difficulty.alien_accuracy.min(1.);
+        difficulty
+    }
+
+    pub fn enemy_weapon_cooldown(&self, base_cooldown_seconds: f64) -> f64 {
+        if self.alien_fire_rate <= 0. {
+            return f64::MAX;
+        }
+        base_cooldown_seconds / self.alien_fire_rate as f64
+    }
+
+    pub fn lethal_speed_squared(&self, base_lethal_speed: f32) -> f32 {
+        let lethal_speed = base_lethal_speed * self.lethal_speed_scale;
+        lethal_speed * lethal_speed
+    }
+}
+
+// The Custom values given on the command line, so they can be re-selected from the menu
+pub struct CustomDifficulty(pub Difficulty);
+
+pub struct DifficultyPlugin {
+    pub difficulty: Difficulty,
+}
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(self.difficulty)
+            .add_resource(CustomDifficulty(self.difficulty))
+            .add_system(difficulty_menu_update);
+    }
+}
+
+// The difficulty can be changed from the pause menu
+fn difficulty_menu_update(
+    game_state: Res<GameState>,
+    key_input: Res<Input<KeyCode>>,
+    custom_difficulty: Res<CustomDifficulty>,
+    mut difficulty: ResMut<Difficulty>,
+) {
+    if *game_state != GameState::Paused {
+        return;
+    }
+
+    let mut selected_difficulty = if key_input.just_pressed(KeyCode::F1) {
+        Difficulty::from_preset(DifficultyPreset::Easy)
+    } else if key_input.just_pressed(KeyCode::F2) {
+        Difficulty::from_preset(DifficultyPreset::Normal)
+    } else if key_input.just_pressed(KeyCode::F3) {
+        Difficulty::from_preset(DifficultyPreset::Hard)
+    } else if key_input.just_pressed(KeyCode::F4) {
+        let mut custom = custom_difficulty.0;
+        custom.preset = DifficultyPreset::Custom;
+        custom
+    } else {
+        return;
+    };
+
+    // Orbits were set up for the gravity the mission started with, so only the combat
+    // values change mid-mission
+    selected_difficulty.gravity_scale = difficulty.gravity_scale;
+    *difficulty = selected_difficulty;
+}
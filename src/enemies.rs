@@ -1,11 +1,15 @@
 use bevy::prelude::*;
 use rand::Rng;
 
+use crate::audio::*;
 use crate::boid::*;
 use crate::bullets::*;
 use crate::collision::*;
 use crate::cooldown::*;
+use crate::difficulty::Difficulty;
 use crate::input::*;
+use crate::missiles::*;
+use crate::trail::{RibbonTrail, SHIP_TRAIL_WIDTH};
 use crate::velocity::*;
 pub use crate::EntityType;
 use crate::GameState;
@@ -13,6 +17,10 @@ use crate::GameState;
 pub const ENEMY_WEAPON_COOLDOWN_DURATION: f64 = 2.5;
 pub const ENEMY_BARREL_LENGTH: f32 = 1.2 * crate::PLAYER_SHIP_RADIUS;
 pub const ENEMY_TARGETING_DISTANCE: f32 = 50.0;
+// How far off target, in radians, a shot from an alien with no accuracy can be
+pub const ENEMY_MAX_AIM_ERROR: f32 = 0.3;
+// Chance that a shot is a homing missile rather than a bullet
+pub const ENEMY_MISSILE_CHANCE: f64 = 0.1;
 
 #[derive(Clone, Copy, Default)]
 pub struct Enemy {
@@ -31,12 +39,14 @@ pub fn enemies_update(
     commands: &mut Commands,
 
     game_state: ResMut<GameState>,
+    difficulty: Res<Difficulty>,
 
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut bullet_spawner: ResMut<BulletSpawner>,
 
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+    sound_bank: Res<SoundBank>,
+    audio: Res<SpatialAudio>,
 
     time: Res<Time>,
     mut enemy_query: Query<(Entity, &mut Enemy, &Transform, &mut Velocity)>,
@@ -56,6 +66,9 @@ pub fn enemies_update(
         })
         .collect();
 
+    let mut rng = rand::thread_rng();
+    let aim_error = ENEMY_MAX_AIM_ERROR * (1. - difficulty.alien_accuracy);
+
     for i in 0..enemies.len() {
         if enemies[i].enemy.enemy_weapon_cooldown.over(&time) {
             for (_, player_transform) in player_query.iter_mut() {
@@ -64,25 +77,58 @@ pub fn enemies_update(
                 if (player_position - enemies[i].transform.translation).length()
                     < ENEMY_TARGETING_DISTANCE
                 {
-                    let enemy_facing =
-                        (player_position - enemies[i].transform.translation).normalize();
-                    fire_bullet(
-                        commands,
-                        &mut meshes,
-                        &mut materials,
-                        &asset_server,
-                        &audio,
-                        enemies[i].transform.translation,
-                        enemies[i].velocity,
-                        enemy_facing,
-                        ENEMY_BARREL_LENGTH,
-                    );
+                    let aim_offset = aim_error
+                        * Vec3::new(
+                            rng.gen_range(-1.0, 1.0),
+                            rng.gen_range(-1.0, 1.0),
+                            rng.gen_range(-1.0, 1.0),
+                        );
+                    let enemy_facing = ((player_position - enemies[i].transform.translation)
+                        .normalize()
+                        + aim_offset)
+                        .normalize();
+                    if rng.gen_bool(ENEMY_MISSILE_CHANCE) {
+                        play_sound_from(
+                            &sound_bank,
+                            &audio,
+                            Sound::MissileLaunch,
+                            SoundPosition::Entity(enemies[i].entity),
+                        );
+                        fire_missile(
+                            commands,
+                            &mut meshes,
+                            &mut materials,
+                            &mut bullet_spawner,
+                            &missile_parameters(),
+                            EntityType::Player,
+                            enemies[i].transform.translation,
+                            enemies[i].velocity,
+                            enemy_facing,
+                            ENEMY_BARREL_LENGTH,
+                        );
+                    } else {
+                        fire_bullet(
+                            commands,
+                            &mut meshes,
+                            &mut materials,
+                            &mut bullet_spawner,
+                            &time,
+                            &sound_bank,
+                            &audio,
+                            enemies[i].transform.translation,
+                            enemies[i].velocity,
+                            enemy_facing,
+                            ENEMY_BARREL_LENGTH,
+                        );
+                    }
 
                     match enemy_query.get_component_mut::<Enemy>(enemies[i].entity) {
                         Ok(mut enemy) => {
+                            let cooldown_duration =
+                                difficulty.enemy_weapon_cooldown(ENEMY_WEAPON_COOLDOWN_DURATION);
                             (*enemy)
                                 .enemy_weapon_cooldown
-                                .reset(&time, ENEMY_WEAPON_COOLDOWN_DURATION);
+                                .reset(&time, cooldown_duration);
                         }
                         _ => (),
                     }
@@ -94,69 +140,60 @@ pub fn enemies_update(
     }
 }
 
-pub fn add_enemies(commands: &mut Commands, asset_server: &Res<AssetServer>) {
-    let mut rng = rand::thread_rng();
-
-    let ship_scale = 2.;
-
-    let ship_swarm_count_min = 2;
-    let ship_swarm_count_max = 10;
-    let mut current_enemy_count = 0;
+pub const ENEMY_SHIP_SCALE: f32 = 2.;
+pub const ENEMY_SWARM_SPACING: f32 = 5.0;
 
-    while current_enemy_count < 100 {
-        let mut swarm_count = rng.gen_range(ship_swarm_count_min, ship_swarm_count_max);
-
-        if current_enemy_count + swarm_count > 100 {
-            swarm_count = 100 - current_enemy_count;
-        }
+pub fn spawn_swarm(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    swarm_position: Vec3,
+    swarm_velocity: Vec3,
+    swarm_count: i32,
+) {
+    let mut rng = rand::thread_rng();
 
-        let swarm_span: i32 = ((swarm_count as f64).sqrt()) as i32;
-        let mut swarm_position = Vec3::new(
-            rng.gen_range(-150.0, 150.0),
-            rng.gen_range(-150.0, 150.0),
-            rng.gen_range(-150.0, 150.0),
-        );
-        let initial_swarm_position = swarm_position;
-
-        for i in 0..swarm_count {
-            let ship_mesh_handle =
-                asset_server.load("models/ship/player/PlayerShip01_AA.gltf#Mesh0/Primitive0");
-
-            let ship_material_handle =
-                asset_server.load("models/ship/player/PlayerShip01_AA.gltf#Material0");
-            current_enemy_count += 1;
-            commands
-                .spawn(PbrBundle {
-                    mesh: ship_mesh_handle,
-                    material: ship_material_handle,
-                    transform: Transform {
-                        translation: swarm_position,
-                        scale: Vec3::splat(ship_scale),
-                        ..Default::default()
-                    },
+    let swarm_span: i32 = ((swarm_count as f64).sqrt() as i32).max(1);
+    let mut ship_position = swarm_position;
+
+    for i in 0..swarm_count {
+        let ship_mesh_handle =
+            asset_server.load("models/ship/player/PlayerShip01_AA.gltf#Mesh0/Primitive0");
+
+        let ship_material_handle =
+            asset_server.load("models/ship/player/PlayerShip01_AA.gltf#Material0");
+        commands
+            .spawn(PbrBundle {
+                mesh: ship_mesh_handle,
+                material: ship_material_handle,
+                transform: Transform {
+                    translation: ship_position,
+                    scale: Vec3::splat(ENEMY_SHIP_SCALE),
                     ..Default::default()
-                })
-                .with(Collision {
-                    mass: 4.,
-                    radius: 2.,
-                    etype: EntityType::Alien,
-                })
-                .with(Boid::default())
-                .with(Enemy::default())
-                .with(Velocity {
-                    velocity: Vec3::new(
+                },
+                ..Default::default()
+            })
+            .with(Collision {
+                mass: 4.,
+                radius: 2.,
+                etype: EntityType::Alien,
+            })
+            .with(Boid::default())
+            .with(Enemy::default())
+            .with(RibbonTrail::new(SHIP_TRAIL_WIDTH))
+            .with(Velocity {
+                velocity: swarm_velocity
+                    + Vec3::new(
                         rng.gen_range(-5.0, 5.0),
                         rng.gen_range(-5.0, 5.0),
                         rng.gen_range(-5.0, 5.0),
                     ),
-                });
+            });
 
-            if i % swarm_span == 0 {
-                swarm_position.y += 5.0;
-                swarm_position.x = initial_swarm_position.x;
-            } else {
-                swarm_position.x += 5.0;
-            }
+        if i % swarm_span == 0 {
+            ship_position.y += ENEMY_SWARM_SPACING;
+            ship_position.x = swarm_position.x;
+        } else {
+            ship_position.x += ENEMY_SWARM_SPACING;
         }
     }
 }
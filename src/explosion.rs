@@ -1,124 +1,215 @@
 use bevy::prelude::*;
-use bevy::core::Timer;
 
-use crate::velocity::Velocity;
-
-use ezing;
+use crate::asteroids::AsteroidDamageEvent;
+use crate::audio::*;
+use crate::bullets::BulletSpawner;
+use crate::camera_effects::CameraEffectEvent;
+use crate::collision::{hit_severity, Collision, EntityType};
+use crate::difficulty::Difficulty;
+use crate::particles::{ParticleKind, Particles};
+use crate::velocity::*;
+use crate::GameState;
+
+// How far a ship blowing up reaches, and the impulse it gives a body caught right in it
+pub const EXPLOSION_BLAST_RADIUS: f32 = 12.;
+pub const EXPLOSION_BLAST_STRENGTH: f32 = 30.;
+
+// Missiles and mines carry warheads, which blast further and harder
+pub const WARHEAD_BLAST_RADIUS: f32 = 20.;
+pub const WARHEAD_BLAST_STRENGTH: f32 = 60.;
+
+// Damage done to an asteroid for each unit of impulse a blast gives it
+const BLAST_ASTEROID_DAMAGE: f32 = 1.;
+
+// Kicks from a blast faster than these destroy a ship, scaled by the difficulty like
+// collisions are. Aliens are flimsy so explosions chain through a swarm, while the player
+// only dies close to a warhead, never from an alien blowing up nearby.
+const BLAST_LETHAL_SPEED_OF_ALIEN: f32 = 3.;
+const BLAST_LETHAL_SPEED_OF_PLAYER: f32 = 10.;
 
 pub struct ExplosionEvent {
-    position: Vec3,
-    velocity: Vec3
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub radius: f32,
+    pub strength: f32,
 }
 
 impl ExplosionEvent {
     pub fn new(pos: Vec3, velocity: Vec3) -> ExplosionEvent {
-        ExplosionEvent{ position: pos, velocity: velocity }
+        ExplosionEvent {
+            position: pos,
+            velocity: velocity,
+            radius: EXPLOSION_BLAST_RADIUS,
+            strength: EXPLOSION_BLAST_STRENGTH,
+        }
+    }
+
+    pub fn warhead(pos: Vec3, velocity: Vec3) -> ExplosionEvent {
+        ExplosionEvent {
+            position: pos,
+            velocity: velocity,
+            radius: WARHEAD_BLAST_RADIUS,
+            strength: WARHEAD_BLAST_STRENGTH,
+        }
     }
 }
 
 pub struct ExplosionPlugin;
 
-fn lerp(t:f32, a:f32, b:f32) -> f32 {
-    a + (b-a) * t
-}
-
 impl Plugin for ExplosionPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app
-        .add_resource(ExplosionSpawner::new())
-        .add_event::<ExplosionEvent>()
-        .add_startup_system(explosion_setup)
-        .add_system(explosion_update);  
+        app.add_event::<ExplosionEvent>()
+            .add_system(explosion_update)
+            // After the update stage, so whatever blew up has already been despawned
+            .add_system_to_stage(stage::POST_UPDATE, explosion_blast_update);
     }
-    // span 1 quad, re-draw very 60ms
-}
-
-// Setting
-const EXPLOSION_MAX_SCALE:f32 = 3.;
-const EXPLOSION_TIME_DURATION_IN_SECONDS:f32 = 0.3;
-
-
-// internal constants
-const EXPLOSION_INIT_SCALE:f32 = 0.25;
-
-struct ExplosionSpawner {
-    expl_material: Handle<StandardMaterial>,
-    expl_mesh: Handle<Mesh>,
 }
 
-
-
-impl ExplosionSpawner {
-
-    fn new() -> ExplosionSpawner {
-        ExplosionSpawner{ 
-            expl_material: Default::default(), 
-            expl_mesh: Default::default(),
-        }
-    }
-
-    fn spawn_explosion(&self,
-        commands: &mut Commands,
-        event: &ExplosionEvent,
-    ) {
-        commands.spawn(PbrBundle {
-            mesh: self.expl_mesh.clone(),
-            material: self.expl_material.clone(),
-            transform: Transform {
-                translation: event.position,
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-            .with(ExplosionInstance{ timer: Timer::from_seconds(EXPLOSION_TIME_DURATION_IN_SECONDS, false)})
-            .with(Velocity::new(event.velocity));
-    }
-
-    fn setup(
-        &mut self,
-        meshes: &mut ResMut<Assets<Mesh>>,
-        materials: &mut ResMut<Assets<StandardMaterial>>,
-    ) {
-        self.expl_material = materials.add(Color::rgb(1.0, 0.1, 0.1).into());
-        self.expl_mesh = meshes.add(Mesh::from(shape::Icosphere::default()));
-    }
-}
-
-struct ExplosionInstance {
-    timer: Timer,
-}
-
-fn explosion_setup(
-    mut explostion_spawner: ResMut<ExplosionSpawner>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    explostion_spawner.setup(&mut meshes, &mut materials);
-}
+// An explosion is a fireball, with sparks and debris thrown out of it
+const EXPLOSION_PARTICLES: [ParticleKind; 3] = [
+    ParticleKind::Fire,
+    ParticleKind::Spark,
+    ParticleKind::Debris,
+];
 
 fn explosion_update(
-    time: Res<Time>,
     mut explosion_queue: Local<EventReader<ExplosionEvent>>,
     explosion_events: Res<Events<ExplosionEvent>>,
-    spawner : Res<ExplosionSpawner>,
-    commands: &mut Commands,
-    mut explosions_query: Query<(Entity, &mut ExplosionInstance, &mut Transform)>
+    mut particles: ResMut<Particles>,
 ) {
-    
     for event in explosion_queue.iter(&explosion_events) {
-        spawner.spawn_explosion(commands, event);
+        let scale = event.radius / EXPLOSION_BLAST_RADIUS;
+        for kind in EXPLOSION_PARTICLES.iter() {
+            particles.burst(*kind, event.position, event.velocity, Vec3::zero(), scale);
+        }
     }
+}
 
-    for (entity, mut explosion_info, mut transform) in explosions_query.iter_mut() {
-
-        explosion_info.timer.tick(time.delta_seconds);
-
-        let normalized_time = explosion_info.timer.elapsed() / EXPLOSION_TIME_DURATION_IN_SECONDS;
-        let new_scale = lerp(ezing::sine_out(normalized_time), EXPLOSION_INIT_SCALE, EXPLOSION_MAX_SCALE);
-        transform.scale = Vec3::splat(new_scale);
+// Blasts push everything nearby away, harder the closer it is. Aliens and the player are
+// destroyed by a hard enough kick, and missiles and mines caught in a blast go off too, so
+// explosions can chain through a tight swarm.
+fn explosion_blast_update(
+    commands: &mut Commands,
+    mut explosion_queue: Local<EventReader<ExplosionEvent>>,
+    mut explosion_events: ResMut<Events<ExplosionEvent>>,
+    mut asteroid_damage_events: ResMut<Events<AsteroidDamageEvent>>,
+    mut camera_effect_events: ResMut<Events<CameraEffectEvent>>,
+    mut bullet_spawner: ResMut<BulletSpawner>,
+    mut game_state: ResMut<GameState>,
+    time: Res<Time>,
+    difficulty: Res<Difficulty>,
+    sound_bank: Res<SoundBank>,
+    audio: Res<SpatialAudio>,
+    mut query: Query<(Entity, &Transform, &mut Velocity, &Collision)>,
+) {
+    // Read every blast before sending the explosions they set off
+    let blasts: Vec<(Vec3, f32, f32)> = explosion_queue
+        .iter(&explosion_events)
+        .map(|event| (event.position, event.radius, event.strength))
+        .collect();
+
+    if *game_state != GameState::Running {
+        return;
+    }
 
-        if explosion_info.timer.just_finished() {
-            commands.despawn(entity);
+    let lethal_speed_of_alien_squared =
+        difficulty.lethal_speed_squared(BLAST_LETHAL_SPEED_OF_ALIEN);
+    let lethal_speed_of_player_squared =
+        difficulty.lethal_speed_squared(BLAST_LETHAL_SPEED_OF_PLAYER);
+
+    let mut destroyed = Vec::new();
+    for (blast_position, radius, strength) in blasts {
+        for (entity, transform, mut velocity, collision) in query.iter_mut() {
+            if destroyed.contains(&entity) {
+                continue;
+            }
+
+            let offset = transform.translation - blast_position;
+            let distance = offset.length();
+            let surface_distance = (distance - collision.radius).max(0.);
+            if surface_distance >= radius {
+                continue;
+            }
+
+            let impulse = strength * (1. - surface_distance / radius);
+            let direction = if distance > 0. {
+                offset / distance
+            } else {
+                Vec3::zero()
+            };
+            let delta_v = impulse / collision.mass;
+
+            match collision.etype {
+                // Planets don't budge, and bullets are too small to matter
+                EntityType::Planet | EntityType::Bullet => {}
+                EntityType::Missile | EntityType::Mine => {
+                    play_sound_at(
+                        &sound_bank,
+                        &audio,
+                        Sound::EnemyExplode,
+                        transform.translation,
+                        velocity.velocity,
+                    );
+                    if collision.etype == EntityType::Mine {
+                        bullet_spawner.despawn(commands, &time, entity);
+                    } else {
+                        commands.despawn(entity);
+                    }
+                    explosion_events.send(ExplosionEvent::warhead(
+                        transform.translation,
+                        velocity.velocity,
+                    ));
+                    destroyed.push(entity);
+                }
+                EntityType::Asteroid => {
+                    velocity.velocity += direction * delta_v;
+                    asteroid_damage_events.send(AsteroidDamageEvent {
+                        entity,
+                        damage: impulse * BLAST_ASTEROID_DAMAGE,
+                    });
+                }
+                EntityType::Alien => {
+                    velocity.velocity += direction * delta_v;
+                    if delta_v * delta_v > lethal_speed_of_alien_squared {
+                        play_sound_at(
+                            &sound_bank,
+                            &audio,
+                            Sound::EnemyExplode,
+                            transform.translation,
+                            velocity.velocity,
+                        );
+                        commands.despawn(entity);
+                        explosion_events.send(ExplosionEvent::new(
+                            transform.translation,
+                            velocity.velocity,
+                        ));
+                        destroyed.push(entity);
+                    }
+                }
+                EntityType::Player => {
+                    velocity.velocity += direction * delta_v;
+                    camera_effect_events.send(CameraEffectEvent::Hit(hit_severity(
+                        delta_v * delta_v,
+                        lethal_speed_of_player_squared,
+                    )));
+                    if delta_v * delta_v > lethal_speed_of_player_squared {
+                        play_sound_at(
+                            &sound_bank,
+                            &audio,
+                            Sound::PlayerCrash,
+                            transform.translation,
+                            velocity.velocity,
+                        );
+                        commands.despawn(entity);
+                        explosion_events.send(ExplosionEvent::new(
+                            transform.translation,
+                            velocity.velocity,
+                        ));
+                        destroyed.push(entity);
+                        *game_state = GameState::Lost;
+                    }
+                }
+            }
         }
     }
-}
\ No newline at end of file
+}
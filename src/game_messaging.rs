@@ -1,3 +1,6 @@
+use crate::difficulty::*;
+use crate::planets::Planet;
+use crate::waves::WaveDirector;
 use crate::GameState;
 use bevy::prelude::*;
 
@@ -7,6 +10,10 @@ pub struct GameMessagePlugin;
 
 struct PauseMessageMarker;
 
+struct DifficultyMessageMarker;
+
+struct WaveMessageMarker;
+
 struct GameMessageState {
     displayed: bool,
     pause_menu_displayed: bool,
@@ -15,8 +22,10 @@ struct GameMessageState {
 impl Plugin for GameMessagePlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_startup_system(setup_ui)
+            .add_startup_system(setup_wave_message)
             .add_resource(GameMessageState { displayed: false, pause_menu_displayed: false })
-            .add_system(update_ui);
+            .add_system(update_ui)
+            .add_system(wave_message_update);
     }
     // span 1 quad, re-draw very 60ms
 }
@@ -46,6 +55,42 @@ fn spawn_pause_message(commands: &mut Commands,
         String::from("click the screen to unpause"), 
         position,
         true);
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Px(40.0)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Percent(55.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: String::new(),
+                font,
+                style: TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    alignment: TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        vertical: VerticalAlign::Top,
+                    },
+                },
+            },
+            ..Default::default()
+        })
+        .with(PauseMessageMarker)
+        .with(DifficultyMessageMarker);
+}
+
+fn difficulty_message(difficulty: &Difficulty) -> String {
+    format!(
+        "difficulty: {:?}  (F1 easy, F2 normal, F3 hard, F4 custom)",
+        difficulty.preset
+    )
 }
 
 fn spawn_message(commands: &mut Commands, 
@@ -88,22 +133,76 @@ fn setup_ui(
     commands.spawn(UiCameraBundle::default());
 }
 
+fn setup_wave_message(
+    commands: &mut Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Px(40.0)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: String::new(),
+                font,
+                style: TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                    alignment: TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        vertical: VerticalAlign::Top,
+                    },
+                },
+            },
+            ..Default::default()
+        })
+        .with(WaveMessageMarker);
+}
+
+// The wave number along the top of the screen, cleared when the game is over
+fn wave_message_update(
+    game_state: Res<GameState>,
+    director: Res<WaveDirector>,
+    mut wave_msg_query: Query<(&WaveMessageMarker, &mut Text)>,
+) {
+    let game_over = *game_state == GameState::Lost || *game_state == GameState::Won;
+    for (_, mut text) in wave_msg_query.iter_mut() {
+        text.value = if director.wave_number > 0 && !game_over {
+            format!("wave {}", director.wave_number)
+        } else {
+            String::new()
+        };
+    }
+}
+
 fn update_ui(
     mut message_state: ResMut<GameMessageState>,
     game_state: Res<GameState>,
     commands: &mut Commands,
     asset_server: Res<AssetServer>,
-    mut pause_msg_query: Query<(&PauseMessageMarker, Entity)>
+    difficulty: Res<Difficulty>,
+    planet_query: Query<&Planet>,
+    mut pause_msg_query: Query<(&PauseMessageMarker, Entity)>,
+    mut difficulty_msg_query: Query<(&DifficultyMessageMarker, &mut Text)>,
 ) {
 
     if !message_state.displayed {
         if *game_state == GameState::Lost {
+            let destroyed_planet = planet_query.iter().find(|planet| planet.destroyed());
+            let message = match destroyed_planet {
+                Some(planet) => format!("{} has been destroyed...", planet.name),
+                None => String::from("You have brought shame to your family..."),
+            };
+
             // spawn "Game Over"
-            spawn_title_message(
-                commands,
-                &asset_server,
-                String::from("You have brought shame to your family..."),
-            );
+            spawn_title_message(commands, &asset_server, message);
             message_state.displayed = true;
         } else if *game_state == GameState::Won {
             // spawn "You Win"
@@ -122,6 +221,10 @@ fn update_ui(
         message_state.pause_menu_displayed = true;
     }
 
+    for (_, mut text) in difficulty_msg_query.iter_mut() {
+        text.value = difficulty_message(&difficulty);
+    }
+
     if remove_pause_menu {
         for (_, entity) in pause_msg_query.iter_mut() {
             commands.despawn(entity);
@@ -1,6 +1,7 @@
 use bevy::math::*;
 use bevy::prelude::*;
 
+use crate::difficulty::Difficulty;
 use crate::velocity::*;
 use crate::GameState;
 
@@ -21,6 +22,7 @@ pub const MIN_GRAVITATION_DISTANCE_SQUARED: f32 =
 pub fn gravity_update(
     time: Res<Time>,
     game_state: ResMut<crate::GameState>,
+    difficulty: Res<Difficulty>,
     mut query: Query<(Entity, &Transform, &mut Velocity, &Gravity)>,
 ) {
     if *game_state != GameState::Running {
@@ -32,6 +34,8 @@ pub fn gravity_update(
         .map(|(e, t, _, g)| (e, t.translation, *g))
         .collect();
 
+    let gravitational_constant = GRAVITATIONAL_CONSTANT * difficulty.gravity_scale;
+
     let object_count = objects.len();
 
     for i in 0..object_count {
@@ -49,7 +53,7 @@ pub fn gravity_update(
             let dist_recip = dist_squared_recip.sqrt();
             let direction = displacement * dist_recip;
 
-            let force = GRAVITATIONAL_CONSTANT * m1 * m2 * dist_squared_recip;
+            let force = gravitational_constant * m1 * m2 * dist_squared_recip;
             let impulse_magnitude = force * time.delta_seconds;
 
             let impulse = impulse_magnitude * direction;
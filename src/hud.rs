@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+
+use crate::collision::*;
+use crate::difficulty::Difficulty;
+use crate::input::{CameraInput, PlayerInput};
+use crate::planets::{Planet, PlanetRole};
+use crate::velocity::*;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_flight_hud)
+            .add_system(flight_hud_update)
+            .add_system(flight_markers_update);
+    }
+}
+
+pub const HUD_MARKER_FONT_SIZE: f32 = 24.;
+// Directions are projected from a point this far in front of the camera
+const HUD_DIRECTION_DISTANCE: f32 = 100.;
+
+// Projects a point in the world to window coordinates from the top left,
+// or None if it is behind the camera
+pub fn world_to_screen(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    window: &Window,
+    world_position: Vec3,
+) -> Option<Vec2> {
+    let view = camera_transform.compute_matrix().inverse();
+    let clip = camera.projection_matrix
+        * view
+        * Vec4::new(world_position.x, world_position.y, world_position.z, 1.);
+    if clip.w <= 0. {
+        return None;
+    }
+
+    let ndc_x = clip.x / clip.w;
+    let ndc_y = clip.y / clip.w;
+    Some(Vec2::new(
+        (ndc_x + 1.) / 2. * window.width(),
+        (1. - ndc_y) / 2. * window.height(),
+    ))
+}
+
+// Places an absolutely positioned UI node so its centre is on a point of the window
+pub fn place_hud_marker(style: &mut Style, screen_position: Vec2, size: f32) {
+    style.position.left = Val::Px(screen_position.x - size / 2.);
+    style.position.top = Val::Px(screen_position.y - size / 2.);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlightMarker {
+    // Where the player is heading relative to the nearest body
+    Prograde,
+    Retrograde,
+    Target,
+}
+
+impl FlightMarker {
+    fn symbol(&self) -> &'static str {
+        match self {
+            FlightMarker::Prograde => "+",
+            FlightMarker::Retrograde => "x",
+            FlightMarker::Target => "[ ]",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            FlightMarker::Prograde => Color::rgb(0.3, 1.0, 0.3),
+            FlightMarker::Retrograde => Color::rgb(1.0, 0.8, 0.2),
+            FlightMarker::Target => Color::rgb(0.3, 0.7, 1.0),
+        }
+    }
+}
+
+struct FlightHudMarker;
+
+fn setup_flight_hud(commands: &mut Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    left: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: String::new(),
+                font: font.clone(),
+                style: TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                    alignment: TextAlignment::default(),
+                },
+            },
+            ..Default::default()
+        })
+        .with(FlightHudMarker);
+
+    for marker in [
+        FlightMarker::Prograde,
+        FlightMarker::Retrograde,
+        FlightMarker::Target,
+    ]
+    .iter()
+    {
+        commands
+            .spawn(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                text: Text {
+                    value: String::new(),
+                    font: font.clone(),
+                    style: TextStyle {
+                        font_size: HUD_MARKER_FONT_SIZE,
+                        color: marker.color(),
+                        alignment: TextAlignment::default(),
+                    },
+                },
+                ..Default::default()
+            })
+            .with(*marker);
+    }
+}
+
+struct ReferenceBody {
+    name: String,
+    position: Vec3,
+    velocity: Vec3,
+    radius: f32,
+}
+
+// The body the player's speed is measured against: the nearest planet or asteroid surface
+fn nearest_body(
+    player_position: Vec3,
+    body_query: &Query<(&Transform, &Velocity, &Collision, Option<&Planet>)>,
+) -> Option<ReferenceBody> {
+    body_query
+        .iter()
+        .filter(|(_, _, c, _)| c.etype == EntityType::Planet || c.etype == EntityType::Asteroid)
+        .map(|(t, v, c, planet)| {
+            let surface_distance = (t.translation - player_position).length() - c.radius;
+            let name = match planet {
+                Some(planet) => planet.name.clone(),
+                None => String::from("Asteroid"),
+            };
+            (
+                surface_distance,
+                ReferenceBody {
+                    name,
+                    position: t.translation,
+                    velocity: v.velocity,
+                    radius: c.radius,
+                },
+            )
+        })
+        .min_by(|(d1, _), (d2, _)| d1.partial_cmp(d2).unwrap())
+        .map(|(_, body)| body)
+}
+
+// The planet the player is flying to
+fn target_body(
+    body_query: &Query<(&Transform, &Velocity, &Collision, Option<&Planet>)>,
+) -> Option<ReferenceBody> {
+    body_query
+        .iter()
+        .filter_map(|(t, v, c, planet)| match planet {
+            Some(planet) if planet.role == PlanetRole::Target => Some(ReferenceBody {
+                name: planet.name.clone(),
+                position: t.translation,
+                velocity: v.velocity,
+                radius: c.radius,
+            }),
+            _ => None,
+        })
+        .next()
+}
+
+fn flight_hud_update(
+    difficulty: Res<Difficulty>,
+    player_query: Query<(&PlayerInput, &Transform, &Velocity)>,
+    body_query: Query<(&Transform, &Velocity, &Collision, Option<&Planet>)>,
+    mut hud_query: Query<(&FlightHudMarker, &mut Text)>,
+) {
+    let lethal_speed_squared =
+        difficulty.lethal_speed_squared(LETHAL_RELATIVE_VELOCITY_OF_ASTEROID);
+
+    for (_, mut hud_text) in hud_query.iter_mut() {
+        let mut text = String::new();
+        let mut color = Color::WHITE;
+
+        for (_, player_transform, player_velocity) in player_query.iter() {
+            let player_position = player_transform.translation;
+            text.push_str(&format!("SPEED {:.1}\n", player_velocity.velocity.length()));
+
+            if let Some(body) = nearest_body(player_position, &body_query) {
+                let relative_velocity = player_velocity.velocity - body.velocity;
+                let displacement = body.position - player_position;
+                let distance = displacement.length();
+                let closing_speed = if distance > 0. {
+                    relative_velocity.dot(displacement / distance)
+                } else {
+                    0.
+                };
+                text.push_str(&format!(
+                    "REL {} {:.1} (CLOSING {:.1})\nALT {:.0}\n",
+                    body.name.to_uppercase(),
+                    relative_velocity.length(),
+                    closing_speed,
+                    distance - body.radius
+                ));
+
+                // Warn when hitting the nearest body would be fatal
+                if relative_velocity.length_squared() > lethal_speed_squared {
+                    color = Color::rgb(1.0, 0.3, 0.3);
+                }
+            }
+
+            if let Some(target) = target_body(&body_query) {
+                let distance = (target.position - player_position).length() - target.radius;
+                text.push_str(&format!(
+                    "TARGET {} {:.0}\n",
+                    target.name.to_uppercase(),
+                    distance.max(0.)
+                ));
+            }
+        }
+
+        hud_text.value = text;
+        hud_text.style.color = color;
+    }
+}
+
+fn flight_markers_update(
+    windows: Res<Windows>,
+    camera_query: Query<(&CameraInput, &Camera, &GlobalTransform)>,
+    player_query: Query<(&PlayerInput, &Transform, &Velocity)>,
+    body_query: Query<(&Transform, &Velocity, &Collision, Option<&Planet>)>,
+    mut marker_query: Query<(&FlightMarker, &mut Style, &mut Text)>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let mut screen_positions: Vec<(FlightMarker, Vec2)> = Vec::new();
+
+    for (_, camera, camera_transform) in camera_query.iter() {
+        for (_, player_transform, player_velocity) in player_query.iter() {
+            let player_position = player_transform.translation;
+
+            let relative_velocity = match nearest_body(player_position, &body_query) {
+                Some(body) => player_velocity.velocity - body.velocity,
+                None => player_velocity.velocity,
+            };
+            if relative_velocity.length_squared() > 0. {
+                let direction = relative_velocity.normalize() * HUD_DIRECTION_DISTANCE;
+                let camera_position = camera_transform.translation;
+                for (marker, point) in [
+                    (FlightMarker::Prograde, camera_position + direction),
+                    (FlightMarker::Retrograde, camera_position - direction),
+                ]
+                .iter()
+                {
+                    if let Some(screen_position) =
+                        world_to_screen(camera, camera_transform, window, *point)
+                    {
+                        screen_positions.push((*marker, screen_position));
+                    }
+                }
+            }
+
+            if let Some(target) = target_body(&body_query) {
+                if let Some(screen_position) =
+                    world_to_screen(camera, camera_transform, window, target.position)
+                {
+                    screen_positions.push((FlightMarker::Target, screen_position));
+                }
+            }
+        }
+    }
+
+    for (marker, mut style, mut text) in marker_query.iter_mut() {
+        match screen_positions.iter().find(|(m, _)| m == marker) {
+            Some((_, screen_position)) => {
+                place_hud_marker(&mut style, *screen_position, HUD_MARKER_FONT_SIZE);
+                text.value = String::from(marker.symbol());
+            }
+            // Markers behind the camera are hidden
+            None => text.value = String::new(),
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use bevy::input::mouse::MouseMotion;
 use bevy::prelude::*;
 
-use crate::audio::play_sound;
-use crate::bullets::*;
-use crate::cooldown::*;
+use crate::audio::{play_sound, Sound, SoundBank, SoundId, SpatialAudio};
+use crate::bullets::BulletSpawner;
+use crate::camera::{CameraController, CameraMode};
+use crate::camera_effects::CameraEffectEvent;
+use crate::particles::ParticleEmitter;
 use crate::velocity::*;
+use crate::weapons::*;
 use crate::GameState;
 
 pub struct CameraInput;
@@ -39,44 +42,44 @@ pub fn mouse_move_input_update(
     windows: ResMut<Windows>,
 
     game_state: Res<GameState>,
+    mut camera_controller: ResMut<CameraController>,
 
     mut state: Local<MouseState>,
     mouse_motion_events: Res<Events<MouseMotion>>,
-    mut camera_query: Query<(&CameraInput, &mut Transform)>,
     mut player_query: Query<(&PlayerInput, &mut Transform)>,
 ) {
-    for (_, mut player_transform) in player_query.iter_mut() {
-        for (_, mut camera_transform) in camera_query.iter_mut() {
-            if get_cursor_capture(&windows) && *game_state == GameState::Running {
-                let quat = player_transform.rotation;
-                let rotation_mat = Mat3::from_quat(quat);
+    if !get_cursor_capture(&windows) || *game_state != GameState::Running {
+        return;
+    }
 
-                let mouse_motion_events =
-                    state.mouse_motion_event_reader.iter(&mouse_motion_events);
+    let mouse_motion_events = state.mouse_motion_event_reader.iter(&mouse_motion_events);
 
-                for MouseMotion { delta } in mouse_motion_events {
-                    let yaw_magnitude = -ROTATION_RATE * delta.y;
-                    let pitch_magnitude = -ROTATION_RATE * delta.x;
+    for MouseMotion { delta } in mouse_motion_events {
+        let yaw_magnitude = -ROTATION_RATE * delta.y;
+        let pitch_magnitude = -ROTATION_RATE * delta.x;
 
-                    let yaw = Quat::from_axis_angle(rotation_mat.x_axis, yaw_magnitude);
-                    let pitch = Quat::from_axis_angle(rotation_mat.y_axis, pitch_magnitude);
+        // The free camera looks around by itself, leaving the ship alone
+        if camera_controller.mode == CameraMode::Free {
+            let rotation_mat = Mat3::from_quat(camera_controller.free_rotation);
+            let yaw = Quat::from_axis_angle(rotation_mat.x_axis, yaw_magnitude);
+            let pitch = Quat::from_axis_angle(rotation_mat.y_axis, pitch_magnitude);
+            camera_controller.free_rotation =
+                (yaw * pitch * camera_controller.free_rotation).normalize();
+            continue;
+        }
 
-                    player_transform.rotation = yaw * pitch * player_transform.rotation;
-                    player_transform.rotation = player_transform.rotation.normalize();
-                }
-            }
+        for (_, mut player_transform) in player_query.iter_mut() {
+            let rotation_mat = Mat3::from_quat(player_transform.rotation);
 
-            camera_transform.rotation = player_transform.rotation;
+            let yaw = Quat::from_axis_angle(rotation_mat.x_axis, yaw_magnitude);
+            let pitch = Quat::from_axis_angle(rotation_mat.y_axis, pitch_magnitude);
 
-            let forward = player_transform.forward();
-            let up = player_transform.rotation * Vec3::unit_y();
-            camera_transform.translation = player_transform.translation + forward * 10.0 + up * 2.5;
+            player_transform.rotation = yaw * pitch * player_transform.rotation;
+            player_transform.rotation = player_transform.rotation.normalize();
         }
     }
 }
 
-pub const PLAYER_WEAPON_COOLDOWN_DURATION: f64 = 0.8;
-
 pub const PLAYER_BARREL_LENGTH: f32 = 1.2 * crate::PLAYER_SHIP_RADIUS;
 
 pub fn mouse_button_input_update(
@@ -84,19 +87,19 @@ pub fn mouse_button_input_update(
     commands: &mut Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut bullet_spawner: ResMut<BulletSpawner>,
 
     // For bullet sound effects
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+    sound_bank: Res<SoundBank>,
+    audio: Res<SpatialAudio>,
 
     // For input
     time: Res<Time>,
     mut windows: ResMut<Windows>,
     mouse_button_input: Res<Input<MouseButton>>,
 
-    mut player_weapon_cooldown: Local<Cooldown>,
-
-    mut player_query: Query<(&PlayerInput, &Transform, &mut Velocity)>,
+    mut player_query: Query<(&PlayerInput, &Transform, &Velocity, &mut PlayerWeapons)>,
+    mut camera_effect_events: ResMut<Events<CameraEffectEvent>>,
 
     mut game_state: ResMut<GameState>,
 ) {
@@ -117,34 +120,80 @@ pub fn mouse_button_input_update(
         return;
     }
 
-    if lmb_pressed && player_weapon_cooldown.over(&time) {
-        for (_, transform, velocity) in player_query.iter_mut() {
-            let player_position = transform.translation;
-            let player_velocity = velocity.velocity;
-
-            let quat = transform.rotation;
-            let rotation_mat = Mat3::from_quat(quat);
-
-            // player is looking down the negative-z axis
-            let player_facing = -rotation_mat.z_axis;
-
-            fire_bullet(
-                commands,
-                &mut meshes,
-                &mut materials,
-                &asset_server,
-                &audio,
-                player_position,
-                player_velocity,
-                player_facing,
-                PLAYER_BARREL_LENGTH,
-            );
-            player_weapon_cooldown.reset(&time, PLAYER_WEAPON_COOLDOWN_DURATION);
+    for (_, transform, velocity, mut weapons) in player_query.iter_mut() {
+        let recoil = fire_weapon(
+            commands,
+            &mut meshes,
+            &mut materials,
+            &mut bullet_spawner,
+            &sound_bank,
+            &audio,
+            &time,
+            &mouse_button_input,
+            &mut weapons,
+            transform.translation,
+            velocity.velocity,
+            transform.rotation,
+            PLAYER_BARREL_LENGTH,
+        );
+        if recoil > 0. {
+            camera_effect_events.send(CameraEffectEvent::Shake(recoil));
         }
     }
 }
 
-const THRUSTER_SOUND_DURATION: f64 = 2.5;
+// Camera shake while the main thruster is burning
+const THRUSTER_RUMBLE: f32 = 0.25;
+
+// The strongest thrust, from the main thruster
+const MAX_THRUST: f32 = 20.;
+// How quickly the engine sound follows the throttle, higher is faster
+const THRUSTER_SOUND_SHARPNESS: f32 = 10.;
+const THRUSTER_FADE_OUT_DURATION: f32 = 0.15;
+// Playback speed of the engine sound when idling and at full throttle
+const THRUSTER_MIN_PITCH: f32 = 0.8;
+const THRUSTER_MAX_PITCH: f32 = 1.2;
+
+// The looping engine sound, playing while any thruster is burning
+#[derive(Default)]
+pub struct ThrusterSound {
+    sound: Option<SoundId>,
+    // From 0 to 1, following the thrust
+    throttle: f32,
+    paused: bool,
+}
+
+impl ThrusterSound {
+    fn update(&mut self, audio: &SpatialAudio, throttle: f32, delta_seconds: f32) {
+        let id = match self.sound {
+            Some(id) => id,
+            None => return,
+        };
+        let blend = 1. - (-THRUSTER_SOUND_SHARPNESS * delta_seconds).exp();
+        self.throttle += (throttle - self.throttle) * blend;
+        audio.set_volume(id, self.throttle);
+        audio.set_pitch(
+            id,
+            THRUSTER_MIN_PITCH + (THRUSTER_MAX_PITCH - THRUSTER_MIN_PITCH) * self.throttle,
+        );
+    }
+
+    fn stop(&mut self, audio: &SpatialAudio) {
+        if let Some(id) = self.sound.take() {
+            audio.fade_out(id, THRUSTER_FADE_OUT_DURATION);
+        }
+        self.throttle = 0.;
+    }
+
+    fn set_paused(&mut self, audio: &SpatialAudio, paused: bool) {
+        if let Some(id) = self.sound {
+            if paused != self.paused {
+                audio.set_paused(id, paused);
+            }
+        }
+        self.paused = paused;
+    }
+}
 
 pub fn keyboard_input_update(
     // For input
@@ -154,12 +203,17 @@ pub fn keyboard_input_update(
     keyboard_layout: Res<crate::KeyboardLayout>,
 
     // For thruster sound effects
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
-    mut thruster_sound_cooldown: Local<Cooldown>,
-
-    camera_query: Query<(&CameraInput, &Transform)>,
-    mut player_query: Query<(&PlayerInput, &Transform, &mut Velocity)>,
+    sound_bank: Res<SoundBank>,
+    audio: Res<SpatialAudio>,
+    mut thruster_sound: Local<ThrusterSound>,
+
+    mut player_query: Query<(
+        &PlayerInput,
+        &Transform,
+        &mut Velocity,
+        &mut ParticleEmitter,
+    )>,
+    mut camera_effect_events: ResMut<Events<CameraEffectEvent>>,
 
     game_state: Res<GameState>,
 ) {
@@ -183,8 +237,17 @@ pub fn keyboard_input_update(
         }
     }
 
-    if *game_state != GameState::Running {
-        return;
+    // The engine holds its note while paused, and dies away when the game is over
+    match *game_state {
+        GameState::Running => thruster_sound.set_paused(&audio, false),
+        GameState::Paused => {
+            thruster_sound.set_paused(&audio, true);
+            return;
+        }
+        GameState::Lost | GameState::Won => {
+            thruster_sound.stop(&audio);
+            return;
+        }
     }
 
     use crate::KeyboardLayout::*;
@@ -199,41 +262,52 @@ pub fn keyboard_input_update(
         Colemak => KeyCode::S,
     };
 
-    for (_, transform) in camera_query.iter() {
-        let quat = transform.rotation;
-        let rotation_mat = Mat3::from_quat(quat);
+    let mut throttle = 0.;
+    for (_, transform, mut velocity, mut exhaust) in player_query.iter_mut() {
+        let rotation_mat = Mat3::from_quat(transform.rotation);
 
-        // Camera is looking down the negative-z axis
+        // Thrust follows the ship, not the camera. The ship is looking down the negative-z axis
         let forward = -rotation_mat.z_axis;
         let left = -rotation_mat.x_axis;
 
-        for (_, _transform, mut velocity) in player_query.iter_mut() {
-            // x-forward, y-left
-            let mut acceleration = Vec2::zero();
-            if key_input.pressed(key_forward) {
-                acceleration.x += 20.0;
-            }
-            if key_input.pressed(key_backward) {
-                acceleration.x -= 10.0;
-            }
-            if key_input.pressed(key_left) {
-                acceleration.y += 10.0;
-            }
-            if key_input.pressed(key_right) {
-                acceleration.y -= 10.0;
-            }
+        // x-forward, y-left
+        let mut acceleration = Vec2::zero();
+        if key_input.pressed(key_forward) {
+            acceleration.x += MAX_THRUST;
+        }
+        if key_input.pressed(key_backward) {
+            acceleration.x -= 10.0;
+        }
+        if key_input.pressed(key_left) {
+            acceleration.y += 10.0;
+        }
+        if key_input.pressed(key_right) {
+            acceleration.y -= 10.0;
+        }
 
-            if acceleration != Vec2::zero() && thruster_sound_cooldown.over(&time) {
-                play_sound(
-                    &asset_server,
-                    &audio,
-                    "audio/AmbientThrusterLoopShortened.mp3",
-                );
-                thruster_sound_cooldown.reset(&time, THRUSTER_SOUND_DURATION);
-            }
+        throttle = (acceleration.length() / MAX_THRUST).min(1.);
+        // Exhaust goes the opposite way to the thrust, relative to the ship
+        exhaust.intensity = throttle;
+        if throttle > 0. {
+            exhaust.direction = Vec3::new(acceleration.y, 0., acceleration.x).normalize();
+        }
+        if acceleration.x > 0. {
+            camera_effect_events.send(CameraEffectEvent::Rumble(THRUSTER_RUMBLE));
+        }
+
+        let delta_v = (forward * acceleration.x + left * acceleration.y) * time.delta_seconds;
+        velocity.velocity += delta_v;
+    }
 
-            let delta_v = (forward * acceleration.x + left * acceleration.y) * time.delta_seconds;
-            velocity.velocity += delta_v;
+    // Starts quiet and swells with the throttle, so key presses don't click
+    if throttle > 0. {
+        if thruster_sound.sound.is_none() {
+            let id = play_sound(&sound_bank, &audio, Sound::Thruster);
+            audio.set_volume(id, 0.);
+            thruster_sound.sound = Some(id);
         }
+        thruster_sound.update(&audio, throttle, time.delta_seconds);
+    } else {
+        thruster_sound.stop(&audio);
     }
 }
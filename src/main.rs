@@ -1,28 +1,66 @@
 use std::env;
+use std::process;
 
 use bevy::prelude::*;
 use bevy::render::camera::PerspectiveProjection;
 
+mod args;
+mod asteroid_mesh;
 mod asteroids;
 use crate::asteroids::*;
 mod audio;
 use crate::audio::*;
 mod bullets;
+use crate::bullets::*;
+mod camera;
+use crate::camera::*;
+mod camera_effects;
+use crate::camera_effects::*;
 mod collision;
 mod cooldown;
+mod data;
 use crate::collision::*;
 mod debug;
+mod difficulty;
+use crate::difficulty::*;
 mod enemies;
 use crate::enemies::*;
 mod gravity;
 use crate::gravity::*;
+mod hud;
+use crate::hud::*;
 mod input;
 use crate::input::*;
+mod missiles;
+use crate::missiles::*;
+mod music;
+use crate::music::*;
+mod noise;
+mod orbits;
+mod particles;
+use crate::particles::*;
+mod planets;
+use crate::planets::*;
+mod radar;
+use crate::radar::*;
+mod solar_system;
+use crate::solar_system::*;
+mod sound_bank;
+use crate::sound_bank::*;
+mod targeting;
+use crate::targeting::*;
 mod velocity;
 use crate::velocity::*;
+mod waves;
+use crate::waves::*;
+mod weapons;
+use crate::weapons::*;
 mod boid;
 mod game_messaging;
 mod trail;
+use crate::trail::*;
+mod trajectory;
+use crate::trajectory::*;
 mod explosion;
 use crate::boid::*;
 
@@ -41,18 +79,38 @@ fn main() {
         KeyboardLayout::QWERTY
     };
 
+    let difficulty = Difficulty::from_args(&args);
+    let camera_effects_settings = CameraEffectsSettings::from_args(&args);
+    let motion_trail_settings = MotionTrailSettings::from_args(&args);
+
+    let solar_system = load_or_exit(SolarSystem::load(SOLAR_SYSTEM_FILE));
+    let sound_bank_description = load_or_exit(SoundBankDescription::load(SOUND_BANK_FILE));
+
     App::build()
         .add_resource(Msaa { samples: 4 })
         .add_resource(GameState::Paused)
         .add_resource(keyboard_layout)
+        .add_resource(WaveDirector::new())
+        .add_resource(BulletSpawner::new(!args.contains(&String::from("-no-bullet-pool"))))
+        .add_resource(solar_system)
+        .add_event::<AsteroidDamageEvent>()
+        .add_event::<AsteroidImpactEvent>()
         .add_plugins(DefaultPlugins)
+        .add_plugin(DifficultyPlugin { difficulty })
         //
         // Startup
         .add_startup_system(setup)
         //.add_startup_system(infotext_system)
         //
         // Audio
-        .add_system(play_soundtrack)
+        .add_plugin(SoundBankPlugin {
+            description: sound_bank_description,
+        })
+        .add_plugin(SpatialAudioPlugin {
+            doppler: !args.contains(&String::from("-no-doppler")),
+            mixer: AudioMixer::from_args(&args),
+        })
+        .add_plugin(MusicPlugin)
         //
         // Input
         .add_system(keyboard_input_update)
@@ -61,26 +119,66 @@ fn main() {
         //
         // Gameplay simulation
         .add_system(velocity_update)
+        .add_system(angular_velocity_update)
         .add_system(collision_update)
         .add_system(gravity_update)
         .add_system(enemies_update)
+        .add_system(missiles_update)
+        .add_system(bullets_update)
+        .add_system(asteroid_damage_update)
+        .add_system(asteroid_impact_update)
         .add_system(boid_update)
+        .add_system(wave_director_update)
         //
         // Visuals/UI
         //.add_system(change_text_system)
         .add_system(skybox_update)
         //
+        // Weapons
+        .add_plugin(WeaponsPlugin)
+        //
+        // Planets
+        .add_plugin(PlanetPlugin)
+        .add_plugin(SolarSystemPlugin)
+        //
         // Trail
-        .add_plugin(trail::MotionTrailPlugin)
+        .add_plugin(MotionTrailPlugin {
+            settings: motion_trail_settings,
+        })
+        //
+        // Flight HUD
+        .add_plugin(HudPlugin)
+        .add_plugin(TargetingPlugin)
+        .add_plugin(RadarPlugin)
+        //
+        // Camera
+        .add_plugin(CameraPlugin)
+        .add_plugin(CameraEffectsPlugin {
+            settings: camera_effects_settings,
+        })
+        //
+        // Trajectory prediction
+        .add_plugin(TrajectoryPlugin)
         //
         // Game messaging
         .add_plugin(game_messaging::GameMessagePlugin)
         // 
         // Explosions
         .add_plugin(explosion::ExplosionPlugin)
+        //
+        // Particles
+        .add_plugin(ParticlePlugin)
         .run();
 }
 
+// The game can't run without its data, so stop with the error before opening a window
+fn load_or_exit<T>(loaded: Result<T, String>) -> T {
+    loaded.unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1)
+    })
+}
+
 #[allow(dead_code)]
 #[derive(PartialEq, Eq)]
 pub enum GameState {
@@ -94,10 +192,12 @@ pub enum GameState {
 pub enum EntityType {
     // Passive objects
     Asteroid,
-    Earth,
+    Planet,
 
     // Projectiles
     Bullet,
+    Mine,
+    Missile,
 
     // Ships
     Alien,
@@ -138,6 +238,10 @@ fn add_ship(commands: &mut Commands, asset_server: &Res<AssetServer>, position:
             ..Default::default()
         })
         .with(PlayerInput)
+        .with(PlayerWeapons::default())
+        .with(PredictTrajectory {
+            color: Color::rgb(0.3, 1.0, 0.3),
+        })
         .with(Gravity {
             mass: PLAYER_SHIP_MASS,
         })
@@ -146,34 +250,13 @@ fn add_ship(commands: &mut Commands, asset_server: &Res<AssetServer>, position:
             radius: PLAYER_SHIP_RADIUS,
             etype: EntityType::Player,
         })
-        .with(Velocity::default());
-}
-
-fn add_earth(
-    commands: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<StandardMaterial>>,
-    position: Vec3,
-) {
-    let earth_radius = 5.;
-    let earth_mass = earth_radius * earth_radius;
-    commands
-        .spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Icosphere {
-                radius: earth_radius,
-                ..Default::default()
-            })),
-            material: materials.add(Color::rgb(0.2, 0.2, 1.0).into()),
-            transform: Transform::from_translation(position),
-            ..Default::default()
-        })
-        .with(Gravity { mass: earth_mass })
-        .with(Collision {
-            mass: earth_mass,
-            radius: earth_radius,
-            etype: EntityType::Earth,
-        })
-        .with(Velocity::default());
+        .with(Velocity::default())
+        .with(ParticleEmitter::new(
+            ParticleKind::Exhaust,
+            Vec3::new(0., 0., PLAYER_SHIP_RADIUS),
+            Vec3::unit_z(),
+        ))
+        .with(RibbonTrail::new(SHIP_TRAIL_WIDTH));
 }
 
 fn setup(
@@ -181,6 +264,8 @@ fn setup(
     asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    difficulty: Res<Difficulty>,
+    solar_system: Res<SolarSystem>,
 ) {
     let skybox_mesh_handle = asset_server.load("models/skybox/skybox.gltf#Mesh0/Primitive0");
     let skybox_material_handle = asset_server.load("models/skybox/skybox.gltf#Material0");
@@ -211,13 +296,24 @@ fn setup(
 
     add_ship(commands, &asset_server, Vec3::new(0.0, 0.0, 250.0));
 
-    add_earth(
+    let gravitational_constant = GRAVITATIONAL_CONSTANT * difficulty.gravity_scale;
+
+    let bodies = spawn_solar_system(
         commands,
         &mut meshes,
         &mut materials,
-        Vec3::new(0.0, 0.0, 0.0),
+        gravitational_constant,
+        &solar_system,
     );
 
-    add_asteroids(commands, &mut meshes, &mut materials);
-    add_enemies(commands, &asset_server);
+    // The asteroid field surrounds the planet the player is flying to
+    if let Some(target) = bodies.iter().find(|b| b.role == PlanetRole::Target) {
+        add_asteroids(
+            commands,
+            &mut meshes,
+            &mut materials,
+            gravitational_constant,
+            &target.body,
+        );
+    }
 }
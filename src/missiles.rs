@@ -0,0 +1,285 @@
+use bevy::prelude::*;
+
+use crate::bullets::{BulletSpawner, ProjectileParameters};
+use crate::collision::*;
+use crate::explosion::ExplosionEvent;
+use crate::gravity::*;
+use crate::particles::{ParticleEmitter, ParticleKind};
+use crate::velocity::*;
+use crate::GameState;
+
+pub const MISSILE_LAUNCH_SPEED: f32 = 20.;
+pub const MISSILE_RADIUS: f32 = 0.4;
+pub const MISSILE_MASS: f32 = 0.05;
+
+pub const MISSILE_THRUST: f32 = 60.;
+pub const MISSILE_MAX_TURN_RATE: f32 = 2.5;
+pub const MISSILE_FUEL_DURATION: f32 = 4.;
+pub const MISSILE_LIFETIME: f32 = 10.;
+
+// Proportional navigation gain, 3 to 5 is typical
+pub const MISSILE_NAVIGATION_CONSTANT: f32 = 4.;
+// Keeps the missile steering while the target isn't closing in
+pub const MISSILE_MIN_CLOSING_SPEED: f32 = 10.;
+
+// Targets are only locked within a cone in front of the missile
+pub const MISSILE_LOCK_RANGE: f32 = 150.;
+pub const MISSILE_LOCK_CONE_HALF_ANGLE: f32 = 0.5;
+
+pub struct Missile {
+    pub target: Option<Entity>,
+    // The kind of entity the missile hunts
+    pub target_etype: EntityType,
+    // Seconds of thrust remaining
+    pub fuel: f32,
+    pub lifetime: f32,
+}
+
+pub fn missile_parameters() -> ProjectileParameters {
+    ProjectileParameters {
+        speed: MISSILE_LAUNCH_SPEED,
+        radius: MISSILE_RADIUS,
+        mass: MISSILE_MASS,
+        color: Color::rgb(1.0, 1.0, 0.3),
+        etype: EntityType::Missile,
+        lifetime: MISSILE_LIFETIME,
+        range: f32::MAX,
+    }
+}
+
+pub fn fire_missile(
+    // Systems needed to spawn the missile
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    bullet_spawner: &mut ResMut<BulletSpawner>,
+
+    // Info to spawn the missile
+    projectile: &ProjectileParameters,
+    target_etype: EntityType,
+    shooter_position: Vec3,
+    shooter_velocity: Vec3,
+    shooter_facing: Vec3,
+    shooter_barrel_length: f32,
+) {
+    // Launch clear of the shooter so the missile doesn't collide with it
+    let launch_distance = shooter_barrel_length + 2. * projectile.radius;
+    let missile_position = shooter_position + launch_distance * shooter_facing;
+    let missile_velocity = shooter_velocity + projectile.speed * shooter_facing;
+    let (mesh, material) = bullet_spawner.missile_assets(meshes, materials, projectile);
+
+    commands
+        .spawn(PbrBundle {
+            mesh,
+            material,
+            transform: Transform::from_translation(missile_position)
+                .looking_at(missile_position + shooter_facing, Vec3::unit_y()),
+            ..Default::default()
+        })
+        .with(Missile {
+            target: None,
+            target_etype,
+            fuel: MISSILE_FUEL_DURATION,
+            lifetime: projectile.lifetime,
+        })
+        .with(Gravity {
+            mass: projectile.mass,
+        })
+        .with(Collision {
+            mass: projectile.mass,
+            radius: projectile.radius,
+            etype: projectile.etype,
+        })
+        .with(Velocity {
+            velocity: missile_velocity,
+        })
+        .with(ParticleEmitter::new(
+            ParticleKind::MissileExhaust,
+            Vec3::new(0., 0., projectile.radius * 1.5),
+            Vec3::unit_z(),
+        ));
+}
+
+struct MissileTarget {
+    entity: Entity,
+    position: Vec3,
+    velocity: Vec3,
+    etype: EntityType,
+}
+
+// The nearest target of the right type in the cone in front of the missile
+fn acquire_target(
+    targets: &[MissileTarget],
+    etype: EntityType,
+    missile_position: Vec3,
+    missile_facing: Vec3,
+) -> Option<Entity> {
+    let min_cone_cos = MISSILE_LOCK_CONE_HALF_ANGLE.cos();
+
+    targets
+        .iter()
+        .filter(|t| t.etype == etype)
+        .filter_map(|t| {
+            let displacement = t.position - missile_position;
+            let distance = displacement.length();
+            if distance > MISSILE_LOCK_RANGE || distance <= 0. {
+                return None;
+            }
+            if displacement.dot(missile_facing) / distance < min_cone_cos {
+                return None;
+            }
+            Some((t.entity, distance))
+        })
+        .min_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap())
+        .map(|(e, _)| e)
+}
+
+// True proportional navigation: accelerate perpendicular to the line of sight,
+// proportional to how fast the line of sight is rotating
+fn proportional_navigation_acceleration(
+    missile_position: Vec3,
+    missile_velocity: Vec3,
+    target_position: Vec3,
+    target_velocity: Vec3,
+) -> Vec3 {
+    let line_of_sight = target_position - missile_position;
+    let distance_squared = line_of_sight.length_squared();
+    if distance_squared < MIN_GRAVITATION_DISTANCE_SQUARED {
+        return Vec3::zero();
+    }
+    let line_of_sight_direction = line_of_sight / distance_squared.sqrt();
+
+    let relative_velocity = target_velocity - missile_velocity;
+    let line_of_sight_rotation = line_of_sight.cross(relative_velocity) / distance_squared;
+
+    let closing_speed =
+        (-relative_velocity.dot(line_of_sight_direction)).max(MISSILE_MIN_CLOSING_SPEED);
+
+    MISSILE_NAVIGATION_CONSTANT
+        * closing_speed
+        * line_of_sight_rotation.cross(line_of_sight_direction)
+}
+
+// Rotates towards the desired direction, limited by the maximum turn rate
+fn turn_towards(rotation: Quat, desired_direction: Vec3, max_angle: f32) -> Quat {
+    // Missiles are looking down the negative-z axis
+    let facing = -(rotation * Vec3::unit_z());
+
+    let angle = facing.angle_between(desired_direction);
+    if angle.is_nan() || angle <= 0. {
+        return rotation;
+    }
+
+    let axis = facing.cross(desired_direction);
+    if axis.length_squared() < 1e-8 {
+        // Facing directly away, any perpendicular axis will do
+        let up = rotation * Vec3::unit_y();
+        return (Quat::from_axis_angle(up, max_angle) * rotation).normalize();
+    }
+
+    let turn = Quat::from_axis_angle(axis.normalize(), angle.min(max_angle));
+    (turn * rotation).normalize()
+}
+
+pub fn missiles_update(
+    commands: &mut Commands,
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    mut expl_events: ResMut<Events<ExplosionEvent>>,
+
+    mut missile_query: Query<(
+        Entity,
+        &mut Missile,
+        &mut Transform,
+        &mut Velocity,
+        &mut ParticleEmitter,
+    )>,
+    target_query: Query<(Entity, &Transform, &Velocity, &Collision), Without<Missile>>,
+) {
+    if *game_state != GameState::Running {
+        return;
+    }
+
+    let dt = time.delta_seconds;
+
+    let targets: Vec<MissileTarget> = target_query
+        .iter()
+        .filter(|(_, _, _, c)| c.etype == EntityType::Alien || c.etype == EntityType::Player)
+        .map(|(e, t, v, c)| MissileTarget {
+            entity: e,
+            position: t.translation,
+            velocity: v.velocity,
+            etype: c.etype,
+        })
+        .collect();
+
+    for (entity, mut missile, mut transform, mut velocity, mut exhaust) in missile_query.iter_mut()
+    {
+        missile.lifetime -= dt;
+        if missile.lifetime <= 0. {
+            commands.despawn(entity);
+            expl_events.send(ExplosionEvent::warhead(
+                transform.translation,
+                velocity.velocity,
+            ));
+            continue;
+        }
+
+        let missile_position = transform.translation;
+        let missile_facing = -(transform.rotation * Vec3::unit_z());
+
+        // Reacquire if there is no lock yet or the target has been destroyed
+        let target = missile
+            .target
+            .and_then(|e| targets.iter().find(|t| t.entity == e));
+        let target = match target {
+            Some(target) => Some(target),
+            None => {
+                missile.target = acquire_target(
+                    &targets,
+                    missile.target_etype,
+                    missile_position,
+                    missile_facing,
+                );
+                missile
+                    .target
+                    .and_then(|e| targets.iter().find(|t| t.entity == e))
+            }
+        };
+
+        if missile.fuel <= 0. {
+            // Out of fuel, the missile coasts ballistically
+            exhaust.intensity = 0.;
+            continue;
+        }
+        missile.fuel -= dt;
+        exhaust.intensity = 1.;
+
+        let desired_direction = match target {
+            Some(target) => {
+                let steering = proportional_navigation_acceleration(
+                    missile_position,
+                    velocity.velocity,
+                    target.position,
+                    target.velocity,
+                );
+                let current_direction = if velocity.velocity.length_squared() > 0. {
+                    velocity.velocity.normalize()
+                } else {
+                    missile_facing
+                };
+                (current_direction * MISSILE_THRUST + steering).normalize()
+            }
+            None => missile_facing,
+        };
+
+        transform.rotation = turn_towards(
+            transform.rotation,
+            desired_direction,
+            MISSILE_MAX_TURN_RATE * dt,
+        );
+
+        let thrust_direction = -(transform.rotation * Vec3::unit_z());
+        velocity.velocity += thrust_direction * MISSILE_THRUST * dt;
+    }
+}
@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+
+use crate::audio::*;
+use crate::collision::*;
+use crate::cooldown::*;
+use crate::enemies::Enemy;
+use crate::input::PlayerInput;
+use crate::missiles::Missile;
+use crate::planets::{Planet, PlanetRole};
+use crate::GameState;
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(MusicDirector::default())
+            .add_system(music_update);
+    }
+}
+
+// Each alien this close adds to the intensity
+pub const COMBAT_MUSIC_RANGE: f32 = 200.;
+pub const ALIEN_INTENSITY: f32 = 0.25;
+// For each missile hunting the player
+pub const MISSILE_INTENSITY: f32 = 0.4;
+// Closing in on the target planet builds up to this much intensity
+pub const TARGET_APPROACH_RANGE: f32 = 150.;
+pub const TARGET_APPROACH_INTENSITY: f32 = 0.3;
+// Below this fraction of its health, the target planet is in danger
+pub const LOW_HEALTH_FRACTION: f32 = 0.3;
+pub const LOW_HEALTH_INTENSITY: f32 = 0.5;
+
+// Intensity per second, rising quickly when things kick off and settling slowly
+const INTENSITY_RISE_RATE: f32 = 1.;
+const INTENSITY_FALL_RATE: f32 = 0.15;
+const WARNING_COOLDOWN_DURATION: f64 = 8.;
+
+// Layers the calm and combat tracks by how intense the game is
+#[derive(Default)]
+pub struct MusicDirector {
+    // From 0 for calm to 1 for all-out combat
+    pub intensity: f32,
+    calm_layer: Option<SoundId>,
+    combat_layer: Option<SoundId>,
+    in_danger: bool,
+    warning_cooldown: Cooldown,
+    finished: bool,
+}
+
+struct Intensity {
+    intensity: f32,
+    danger: bool,
+}
+
+fn game_intensity(
+    player_position: Vec3,
+    alien_query: &Query<(&Enemy, &Transform)>,
+    missile_query: &Query<(&Missile, &Transform)>,
+    planet_query: &Query<(&Planet, &Transform, &Collision)>,
+) -> Intensity {
+    let nearby_aliens = alien_query
+        .iter()
+        .filter(|(_, t)| (t.translation - player_position).length() < COMBAT_MUSIC_RANGE)
+        .count();
+    let incoming_missiles = missile_query
+        .iter()
+        .filter(|(m, t)| {
+            m.target_etype == EntityType::Player
+                && (t.translation - player_position).length() < COMBAT_MUSIC_RANGE
+        })
+        .count();
+
+    let mut intensity =
+        nearby_aliens as f32 * ALIEN_INTENSITY + incoming_missiles as f32 * MISSILE_INTENSITY;
+    let mut danger = incoming_missiles > 0;
+
+    for (planet, transform, collision) in planet_query.iter() {
+        if planet.role != PlanetRole::Target {
+            continue;
+        }
+
+        let distance = (transform.translation - player_position).length() - collision.radius;
+        let approach = (1. - distance / TARGET_APPROACH_RANGE).max(0.);
+        intensity += approach * TARGET_APPROACH_INTENSITY;
+
+        if planet.destructible() && planet.health / planet.max_health < LOW_HEALTH_FRACTION {
+            intensity += LOW_HEALTH_INTENSITY;
+            danger = true;
+        }
+    }
+
+    Intensity {
+        intensity: intensity.min(1.),
+        danger,
+    }
+}
+
+fn music_update(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    sound_bank: Res<SoundBank>,
+    audio: Res<SpatialAudio>,
+    mut director: ResMut<MusicDirector>,
+    player_query: Query<(&PlayerInput, &Transform)>,
+    alien_query: Query<(&Enemy, &Transform)>,
+    missile_query: Query<(&Missile, &Transform)>,
+    planet_query: Query<(&Planet, &Transform, &Collision)>,
+) {
+    if director.finished {
+        return;
+    }
+
+    // Both layers play all the time, and only their volumes change
+    let calm_layer = match director.calm_layer {
+        Some(id) => id,
+        None => {
+            let id = play_music(&sound_bank, &audio, Sound::BackgroundMusic);
+            director.calm_layer = Some(id);
+            id
+        }
+    };
+    let combat_layer = match director.combat_layer {
+        Some(id) => id,
+        None => {
+            let id = play_sound(&sound_bank, &audio, Sound::Soundtrack);
+            audio.set_volume(id, 0.);
+            director.combat_layer = Some(id);
+            id
+        }
+    };
+
+    if *game_state == GameState::Won {
+        audio.fade_out(calm_layer, MUSIC_CROSSFADE_DURATION);
+        audio.fade_out(combat_layer, MUSIC_CROSSFADE_DURATION);
+        play_sound(&sound_bank, &audio, Sound::GameWin);
+        director.finished = true;
+        return;
+    }
+
+    let target = match player_query.iter().next() {
+        Some((_, transform)) if *game_state == GameState::Running => game_intensity(
+            transform.translation,
+            &alien_query,
+            &missile_query,
+            &planet_query,
+        ),
+        // Settle down while paused and after losing
+        _ => Intensity {
+            intensity: 0.,
+            danger: false,
+        },
+    };
+
+    let rate = if target.intensity > director.intensity {
+        INTENSITY_RISE_RATE
+    } else {
+        INTENSITY_FALL_RATE
+    };
+    let step = rate * time.delta_seconds;
+    director.intensity += (target.intensity - director.intensity).max(-step).min(step);
+
+    audio.set_volume(calm_layer, 1. - director.intensity);
+    audio.set_volume(combat_layer, director.intensity);
+
+    // The warning sounds when danger starts, but not over and over
+    if target.danger && !director.in_danger && director.warning_cooldown.over(&time) {
+        play_sound(&sound_bank, &audio, Sound::Warning);
+        director
+            .warning_cooldown
+            .reset(&time, WARNING_COOLDOWN_DURATION);
+    }
+    director.in_danger = target.danger;
+}
@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+const NOISE_TABLE_SIZE: usize = 256;
+
+// Smoothly interpolated random values on an integer lattice
+pub struct ValueNoise {
+    permutation: Vec<usize>,
+    values: Vec<f32>,
+}
+
+impl ValueNoise {
+    pub fn new(seed: u64) -> ValueNoise {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut permutation: Vec<usize> = (0..NOISE_TABLE_SIZE).collect();
+        permutation.shuffle(&mut rng);
+
+        let values = (0..NOISE_TABLE_SIZE)
+            .map(|_| rng.gen_range(-1.0, 1.0))
+            .collect();
+
+        ValueNoise {
+            permutation,
+            values,
+        }
+    }
+
+    fn lattice_value(&self, x: i32, y: i32, z: i32) -> f32 {
+        let wrap = |i: i32| (i as usize) & (NOISE_TABLE_SIZE - 1);
+        let hash = self.permutation[wrap(x)];
+        let hash = self.permutation[(hash + wrap(y)) & (NOISE_TABLE_SIZE - 1)];
+        let hash = self.permutation[(hash + wrap(z)) & (NOISE_TABLE_SIZE - 1)];
+        self.values[hash]
+    }
+
+    pub fn sample(&self, point: Vec3) -> f32 {
+        let floor = point.floor();
+        let (x0, y0, z0) = (floor.x as i32, floor.y as i32, floor.z as i32);
+
+        // Smoothstep so the surface has no creases along the lattice
+        let fade = |t: f32| t * t * (3. - 2. * t);
+        let fraction = point - floor;
+        let (tx, ty, tz) = (fade(fraction.x), fade(fraction.y), fade(fraction.z));
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let along_x = |y: i32, z: i32| {
+            lerp(
+                self.lattice_value(x0, y, z),
+                self.lattice_value(x0 + 1, y, z),
+                tx,
+            )
+        };
+        let along_y = |z: i32| lerp(along_x(y0, z), along_x(y0 + 1, z), ty);
+        lerp(along_y(z0), along_y(z0 + 1), tz)
+    }
+
+    // Layers of noise at increasing frequency and decreasing amplitude, roughly in -1..1
+    pub fn fractal(&self, point: Vec3, octaves: u32) -> f32 {
+        let mut total = 0.;
+        let mut amplitude = 1.;
+        let mut frequency = 1.;
+        let mut total_amplitude = 0.;
+        for _ in 0..octaves {
+            total += self.sample(point * frequency) * amplitude;
+            total_amplitude += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.;
+        }
+        total / total_amplitude
+    }
+}
@@ -0,0 +1,106 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+// Plenty for the eccentricities asteroids and moons have
+const KEPLER_SOLVER_ITERATIONS: usize = 8;
+
+// A massive body that things orbit around
+#[derive(Clone, Copy, Debug)]
+pub struct CentralBody {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub mass: f32,
+}
+
+impl CentralBody {
+    // The standard gravitational parameter, G * M
+    pub fn gravitational_parameter(&self, gravitational_constant: f32) -> f32 {
+        gravitational_constant * self.mass
+    }
+}
+
+// Classical Keplerian orbital elements. Orbits with no inclination lie in the
+// x-z plane, and all angles are in radians
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct KeplerOrbit {
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    pub inclination: f32,
+    pub ascending_node: f32,
+    pub argument_of_periapsis: f32,
+    // Where along the orbit the body is, measured from periapsis
+    pub true_anomaly: f32,
+}
+
+impl KeplerOrbit {
+    pub fn circular(radius: f32, inclination: f32, ascending_node: f32, angle: f32) -> KeplerOrbit {
+        KeplerOrbit {
+            semi_major_axis: radius,
+            inclination,
+            ascending_node,
+            true_anomaly: angle,
+            ..Default::default()
+        }
+    }
+
+    // Closest distance to the central body
+    pub fn periapsis(&self) -> f32 {
+        self.semi_major_axis * (1. - self.eccentricity)
+    }
+
+    // The same orbit, moved on by the given number of seconds
+    pub fn advanced(&self, gravitational_parameter: f32, seconds: f32) -> KeplerOrbit {
+        let e = self.eccentricity.max(0.).min(0.99);
+        let shape = (1. - e * e).sqrt();
+
+        let mean_motion = (gravitational_parameter / self.semi_major_axis.powi(3)).sqrt();
+
+        let (sin_anomaly, cos_anomaly) = self.true_anomaly.sin_cos();
+        let eccentric_anomaly = (shape * sin_anomaly).atan2(e + cos_anomaly);
+        let mean_anomaly = eccentric_anomaly - e * eccentric_anomaly.sin() + mean_motion * seconds;
+
+        // Solve Kepler's equation, M = E - e sin E, with Newton's method
+        let mean_anomaly = mean_anomaly % (2. * PI);
+        let mut eccentric_anomaly = if e > 0.8 { PI } else { mean_anomaly };
+        for _ in 0..KEPLER_SOLVER_ITERATIONS {
+            let error = eccentric_anomaly - e * eccentric_anomaly.sin() - mean_anomaly;
+            eccentric_anomaly -= error / (1. - e * eccentric_anomaly.cos());
+        }
+
+        let (sin_eccentric, cos_eccentric) = eccentric_anomaly.sin_cos();
+        KeplerOrbit {
+            true_anomaly: (shape * sin_eccentric).atan2(cos_eccentric - e),
+            ..*self
+        }
+    }
+
+    // Position and velocity relative to the central body.
+    // Only valid for closed orbits, with eccentricity below 1
+    pub fn state(&self, gravitational_parameter: f32) -> (Vec3, Vec3) {
+        let e = self.eccentricity.max(0.).min(0.99);
+        let semi_latus_rectum = self.semi_major_axis * (1. - e * e);
+
+        let (sin_anomaly, cos_anomaly) = self.true_anomaly.sin_cos();
+        let distance = semi_latus_rectum / (1. + e * cos_anomaly);
+
+        // In the plane of the orbit, with periapsis along x
+        let position = Vec3::new(cos_anomaly, 0., sin_anomaly) * distance;
+        let velocity = Vec3::new(-sin_anomaly, 0., e + cos_anomaly)
+            * (gravitational_parameter / semi_latus_rectum).sqrt();
+
+        let orientation = Quat::from_rotation_y(self.ascending_node)
+            * Quat::from_rotation_x(self.inclination)
+            * Quat::from_rotation_y(self.argument_of_periapsis);
+
+        (orientation * position, orientation * velocity)
+    }
+
+    // Absolute position and velocity around the given body
+    pub fn state_around(&self, gravitational_constant: f32, body: &CentralBody) -> (Vec3, Vec3) {
+        let (position, velocity) = self.state(body.gravitational_parameter(gravitational_constant));
+        (body.position + position, body.velocity + velocity)
+    }
+}
@@ -0,0 +1,520 @@
+use bevy::prelude::*;
+use bevy::render::mesh::Indices;
+use bevy::render::pipeline::PrimitiveTopology;
+use rand::Rng;
+
+use crate::input::CameraInput;
+use crate::velocity::Velocity;
+use crate::GameState;
+
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(Particles::default())
+            .add_startup_system(setup_particle_batches)
+            .add_system(particle_emitter_update)
+            .add_system(particle_update)
+            .add_system_to_stage(stage::POST_UPDATE, particle_mesh_update);
+    }
+}
+
+// Older particles are kept over new ones past this many
+pub const MAX_PARTICLES: usize = 4000;
+// Each kind of particle is drawn in this many batches, one for each step of its color curve
+const PARTICLE_COLOR_STEPS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ease {
+    Linear,
+    QuadIn,
+    QuadOut,
+    CubicIn,
+    SineOut,
+}
+
+impl Ease {
+    fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.).min(1.);
+        match self {
+            Ease::Linear => ezing::linear(t),
+            Ease::QuadIn => ezing::quad_in(t),
+            Ease::QuadOut => ezing::quad_out(t),
+            Ease::CubicIn => ezing::cubic_in(t),
+            Ease::SineOut => ezing::sine_out(t),
+        }
+    }
+}
+
+// How a kind of particle is emitted and how it changes over its life
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParticleEffect {
+    // Particles in each burst
+    pub count: u32,
+    // Particles per second from an emitter at full intensity
+    pub rate: f32,
+    // Seconds, picked at random between the two
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    // From 0 for straight along the direction to 1 for every direction
+    pub spread: f32,
+    // Fraction of the velocity of whatever emitted them that particles start with
+    pub inherit_velocity: f32,
+    // Fraction of their own velocity particles lose every second
+    pub drag: f32,
+    pub start_size: f32,
+    pub end_size: f32,
+    pub size_ease: Ease,
+    // Red, green, blue and alpha
+    pub start_color: (f32, f32, f32, f32),
+    pub end_color: (f32, f32, f32, f32),
+    pub color_ease: Ease,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParticleKind {
+    // The flash and fireball of an explosion
+    Fire,
+    // Fast, bright streaks thrown out by explosions
+    Spark,
+    // Slow, long lived chunks left behind by explosions
+    Debris,
+    Exhaust,
+    MissileExhaust,
+    // Where a bullet hits
+    Impact,
+    // Knocked off asteroids when they are hit or break up
+    Dust,
+}
+
+pub const PARTICLE_KINDS: [ParticleKind; 7] = [
+    ParticleKind::Fire,
+    ParticleKind::Spark,
+    ParticleKind::Debris,
+    ParticleKind::Exhaust,
+    ParticleKind::MissileExhaust,
+    ParticleKind::Impact,
+    ParticleKind::Dust,
+];
+
+const FIRE: ParticleEffect = ParticleEffect {
+    count: 24,
+    rate: 0.,
+    lifetime: (0.3, 0.6),
+    speed: (2., 8.),
+    spread: 1.,
+    inherit_velocity: 1.,
+    drag: 2.,
+    start_size: 1.5,
+    end_size: 3.5,
+    size_ease: Ease::QuadOut,
+    start_color: (1.0, 0.9, 0.5, 1.0),
+    end_color: (0.8, 0.1, 0.05, 0.0),
+    color_ease: Ease::QuadIn,
+};
+
+const SPARK: ParticleEffect = ParticleEffect {
+    count: 30,
+    rate: 0.,
+    lifetime: (0.3, 0.8),
+    speed: (20., 45.),
+    spread: 1.,
+    inherit_velocity: 1.,
+    drag: 1.,
+    start_size: 0.25,
+    end_size: 0.05,
+    size_ease: Ease::Linear,
+    start_color: (1.0, 1.0, 0.8, 1.0),
+    end_color: (1.0, 0.4, 0.1, 0.0),
+    color_ease: Ease::Linear,
+};
+
+const DEBRIS: ParticleEffect = ParticleEffect {
+    count: 12,
+    rate: 0.,
+    lifetime: (1.5, 3.),
+    speed: (3., 12.),
+    spread: 1.,
+    inherit_velocity: 1.,
+    drag: 0.1,
+    start_size: 0.4,
+    end_size: 0.3,
+    size_ease: Ease::Linear,
+    start_color: (0.45, 0.4, 0.35, 1.0),
+    end_color: (0.2, 0.2, 0.2, 0.0),
+    color_ease: Ease::CubicIn,
+};
+
+const EXHAUST: ParticleEffect = ParticleEffect {
+    count: 0,
+    rate: 80.,
+    lifetime: (0.2, 0.4),
+    speed: (15., 20.),
+    spread: 0.1,
+    inherit_velocity: 1.,
+    drag: 0.,
+    start_size: 0.4,
+    end_size: 0.9,
+    size_ease: Ease::QuadOut,
+    start_color: (0.6, 0.8, 1.0, 0.9),
+    end_color: (0.2, 0.2, 0.6, 0.0),
+    color_ease: Ease::QuadOut,
+};
+
+const MISSILE_EXHAUST: ParticleEffect = ParticleEffect {
+    count: 0,
+    rate: 60.,
+    lifetime: (0.3, 0.6),
+    speed: (5., 8.),
+    spread: 0.15,
+    inherit_velocity: 1.,
+    drag: 0.5,
+    start_size: 0.2,
+    end_size: 0.6,
+    size_ease: Ease::QuadOut,
+    start_color: (1.0, 0.8, 0.4, 0.9),
+    end_color: (0.4, 0.4, 0.4, 0.0),
+    color_ease: Ease::QuadOut,
+};
+
+const IMPACT: ParticleEffect = ParticleEffect {
+    count: 10,
+    rate: 0.,
+    lifetime: (0.15, 0.35),
+    speed: (8., 20.),
+    spread: 0.6,
+    inherit_velocity: 1.,
+    drag: 3.,
+    start_size: 0.2,
+    end_size: 0.05,
+    size_ease: Ease::Linear,
+    start_color: (0.8, 0.9, 1.0, 1.0),
+    end_color: (0.3, 0.5, 1.0, 0.0),
+    color_ease: Ease::Linear,
+};
+
+const DUST: ParticleEffect = ParticleEffect {
+    count: 16,
+    rate: 0.,
+    lifetime: (0.8, 1.6),
+    speed: (1., 4.),
+    spread: 1.,
+    inherit_velocity: 1.,
+    drag: 0.8,
+    start_size: 0.6,
+    end_size: 1.8,
+    size_ease: Ease::SineOut,
+    start_color: (0.5, 0.45, 0.4, 0.6),
+    end_color: (0.3, 0.3, 0.3, 0.0),
+    color_ease: Ease::Linear,
+};
+
+impl ParticleKind {
+    pub fn effect(self) -> &'static ParticleEffect {
+        match self {
+            ParticleKind::Fire => &FIRE,
+            ParticleKind::Spark => &SPARK,
+            ParticleKind::Debris => &DEBRIS,
+            ParticleKind::Exhaust => &EXHAUST,
+            ParticleKind::MissileExhaust => &MISSILE_EXHAUST,
+            ParticleKind::Impact => &IMPACT,
+            ParticleKind::Dust => &DUST,
+        }
+    }
+
+    fn index(self) -> usize {
+        PARTICLE_KINDS.iter().position(|k| *k == self).unwrap()
+    }
+}
+
+// Emits particles continuously from an entity, like engine exhaust
+pub struct ParticleEmitter {
+    pub kind: ParticleKind,
+    // From 0 to 1, scaling the effect's rate
+    pub intensity: f32,
+    // Where particles come from and the way they go, relative to the entity's rotation
+    pub offset: Vec3,
+    pub direction: Vec3,
+    // Fractions of a particle carried over between frames
+    accumulated: f32,
+}
+
+impl ParticleEmitter {
+    pub fn new(kind: ParticleKind, offset: Vec3, direction: Vec3) -> ParticleEmitter {
+        ParticleEmitter {
+            kind,
+            intensity: 0.,
+            offset,
+            direction,
+            accumulated: 0.,
+        }
+    }
+}
+
+struct Particle {
+    kind: ParticleKind,
+    position: Vec3,
+    velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+    scale: f32,
+}
+
+impl Particle {
+    fn life(&self) -> f32 {
+        self.age / self.lifetime
+    }
+}
+
+#[derive(Default)]
+pub struct Particles {
+    particles: Vec<Particle>,
+}
+
+fn random_unit_vector(rng: &mut impl Rng) -> Vec3 {
+    loop {
+        let v = Vec3::new(
+            rng.gen_range(-1.0, 1.0),
+            rng.gen_range(-1.0, 1.0),
+            rng.gen_range(-1.0, 1.0),
+        );
+        let length_squared = v.length_squared();
+        if length_squared > 1e-4 && length_squared <= 1. {
+            return v / length_squared.sqrt();
+        }
+    }
+}
+
+impl Particles {
+    // A burst of the effect's count of particles. With no direction they fly every way.
+    // The scale grows the size and speed of the burst, for bigger or smaller versions.
+    pub fn burst(
+        &mut self,
+        kind: ParticleKind,
+        position: Vec3,
+        velocity: Vec3,
+        direction: Vec3,
+        scale: f32,
+    ) {
+        let count = kind.effect().count;
+        self.emit(kind, count, position, velocity, direction, scale);
+    }
+
+    fn emit(
+        &mut self,
+        kind: ParticleKind,
+        count: u32,
+        position: Vec3,
+        velocity: Vec3,
+        direction: Vec3,
+        scale: f32,
+    ) {
+        let effect = kind.effect();
+        let mut rng = rand::thread_rng();
+        let direction = if direction.length_squared() > 0. {
+            direction.normalize()
+        } else {
+            Vec3::zero()
+        };
+
+        for _ in 0..count {
+            if self.particles.len() >= MAX_PARTICLES {
+                return;
+            }
+
+            let spread = if direction == Vec3::zero() {
+                1.
+            } else {
+                effect.spread
+            };
+            let particle_direction =
+                (direction * (1. - spread) + random_unit_vector(&mut rng) * spread).normalize();
+            let speed = rng.gen_range(effect.speed.0, effect.speed.1) * scale;
+
+            self.particles.push(Particle {
+                kind,
+                position,
+                velocity: velocity * effect.inherit_velocity + particle_direction * speed,
+                age: 0.,
+                lifetime: rng.gen_range(effect.lifetime.0, effect.lifetime.1),
+                scale,
+            });
+        }
+    }
+}
+
+// One mesh for every particle of a kind at a step of its color curve
+struct ParticleBatch {
+    kind: ParticleKind,
+    step: usize,
+    mesh: Handle<Mesh>,
+}
+
+fn lerp_color(from: (f32, f32, f32, f32), to: (f32, f32, f32, f32), t: f32) -> Color {
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    Color::rgba(
+        lerp(from.0, to.0),
+        lerp(from.1, to.1),
+        lerp(from.2, to.2),
+        lerp(from.3, to.3),
+    )
+}
+
+fn quads_mesh(positions: Vec<[f32; 3]>, normal: Vec3) -> Mesh {
+    let quad_count = positions.len() / 4;
+    let normals: Vec<[f32; 3]> = vec![normal.into(); positions.len()];
+    let uvs: Vec<[f32; 2]> = (0..quad_count)
+        .flat_map(|_| vec![[0., 0.], [1., 0.], [1., 1.], [0., 1.]])
+        .collect();
+    let indices: Vec<u32> = (0..quad_count as u32)
+        .flat_map(|i| {
+            let v = i * 4;
+            vec![v, v + 1, v + 2, v, v + 2, v + 3]
+        })
+        .collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn setup_particle_batches(
+    commands: &mut Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for kind in PARTICLE_KINDS.iter() {
+        let effect = kind.effect();
+        for step in 0..PARTICLE_COLOR_STEPS {
+            let t = step as f32 / (PARTICLE_COLOR_STEPS - 1) as f32;
+            let mesh = meshes.add(quads_mesh(Vec::new(), Vec3::unit_z()));
+
+            commands
+                .spawn(PbrBundle {
+                    mesh: mesh.clone(),
+                    material: materials.add(StandardMaterial {
+                        albedo: lerp_color(effect.start_color, effect.end_color, t),
+                        shaded: false,
+                        ..Default::default()
+                    }),
+                    visible: Visible {
+                        is_visible: false,
+                        is_transparent: true,
+                    },
+                    ..Default::default()
+                })
+                .with(ParticleBatch {
+                    kind: *kind,
+                    step,
+                    mesh,
+                });
+        }
+    }
+}
+
+fn particle_emitter_update(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    mut particles: ResMut<Particles>,
+    mut emitter_query: Query<(&mut ParticleEmitter, &Transform, Option<&Velocity>)>,
+) {
+    if *game_state == GameState::Paused {
+        return;
+    }
+
+    for (mut emitter, transform, velocity) in emitter_query.iter_mut() {
+        if emitter.intensity <= 0. {
+            emitter.accumulated = 0.;
+            continue;
+        }
+
+        let effect = emitter.kind.effect();
+        emitter.accumulated += effect.rate * emitter.intensity * time.delta_seconds;
+        let count = emitter.accumulated.floor();
+        emitter.accumulated -= count;
+
+        let velocity = velocity.map(|v| v.velocity).unwrap_or_else(Vec3::zero);
+        particles.emit(
+            emitter.kind,
+            count as u32,
+            transform.translation + transform.rotation * emitter.offset,
+            velocity,
+            transform.rotation * emitter.direction,
+            1.,
+        );
+    }
+}
+
+fn particle_update(time: Res<Time>, game_state: Res<GameState>, mut particles: ResMut<Particles>) {
+    // Particles hang in the air while paused, but play out after the game ends
+    if *game_state == GameState::Paused {
+        return;
+    }
+
+    let dt = time.delta_seconds;
+    particles
+        .particles
+        .retain(|particle| particle.age + dt < particle.lifetime);
+    for particle in particles.particles.iter_mut() {
+        let drag = particle.kind.effect().drag;
+        particle.age += dt;
+        particle.velocity *= (1. - drag * dt).max(0.);
+        particle.position += particle.velocity * dt;
+    }
+}
+
+fn particle_mesh_update(
+    particles: Res<Particles>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    camera_query: Query<(&CameraInput, &Transform)>,
+    mut batch_query: Query<(&ParticleBatch, &mut Visible)>,
+) {
+    let camera_rotation = match camera_query.iter().next() {
+        Some((_, transform)) => transform.rotation,
+        None => return,
+    };
+    // Quads face the camera
+    let right = camera_rotation * Vec3::unit_x();
+    let up = camera_rotation * Vec3::unit_y();
+    let normal = camera_rotation * Vec3::unit_z();
+
+    let batch_count = PARTICLE_KINDS.len() * PARTICLE_COLOR_STEPS;
+    let mut positions: Vec<Vec<[f32; 3]>> = vec![Vec::new(); batch_count];
+    for particle in particles.particles.iter() {
+        let effect = particle.kind.effect();
+        let life = particle.life();
+
+        let color_t = effect.color_ease.apply(life);
+        let step = (color_t * (PARTICLE_COLOR_STEPS - 1) as f32).round() as usize;
+        let size_t = effect.size_ease.apply(life);
+        let size =
+            (effect.start_size + (effect.end_size - effect.start_size) * size_t) * particle.scale;
+
+        let half_right = right * size / 2.;
+        let half_up = up * size / 2.;
+        let p = particle.position;
+        let batch = &mut positions[particle.kind.index() * PARTICLE_COLOR_STEPS + step];
+        batch.push((p - half_right - half_up).into());
+        batch.push((p + half_right - half_up).into());
+        batch.push((p + half_right + half_up).into());
+        batch.push((p - half_right + half_up).into());
+    }
+
+    for (batch, mut visible) in batch_query.iter_mut() {
+        let batch_positions =
+            std::mem::take(&mut positions[batch.kind.index() * PARTICLE_COLOR_STEPS + batch.step]);
+        let was_visible = visible.is_visible;
+        visible.is_visible = !batch_positions.is_empty();
+
+        // Empty batches are left alone, rather than rebuilt every frame
+        if !visible.is_visible && !was_visible {
+            continue;
+        }
+        if let Some(mesh) = meshes.get_mut(&batch.mesh) {
+            *mesh = quads_mesh(batch_positions, normal);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::audio::*;
+use crate::explosion::ExplosionEvent;
+use crate::velocity::*;
+use crate::GameState;
+
+pub struct PlanetPlugin;
+
+impl Plugin for PlanetPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<PlanetImpactEvent>()
+            .add_event::<PlanetDestroyedEvent>()
+            .add_event::<PlanetContactEvent>()
+            .add_startup_system(setup_planet_hud)
+            .add_system(planet_impact_update)
+            .add_system(planet_contact_update)
+            .add_system(planet_hud_update);
+    }
+}
+
+// Scales impact energy into damage to the planet
+pub const PLANET_IMPACT_DAMAGE_SCALE: f32 = 1.;
+
+// What a planet or moon means for the mission
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum PlanetRole {
+    // Reach it to win, lose it to lose
+    Target,
+    // Destroys ships that touch it
+    Hazard,
+    // Only there for its gravity, ships bounce off it
+    GravityAssist,
+}
+
+pub struct Planet {
+    pub name: String,
+    pub role: PlanetRole,
+    // Planets with no health can't be destroyed
+    pub health: f32,
+    pub max_health: f32,
+}
+
+impl Planet {
+    pub fn new(name: &str, role: PlanetRole, max_health: f32) -> Planet {
+        Planet {
+            name: String::from(name),
+            role,
+            health: max_health,
+            max_health,
+        }
+    }
+
+    pub fn destructible(&self) -> bool {
+        self.max_health > 0.
+    }
+
+    pub fn destroyed(&self) -> bool {
+        self.destructible() && self.health <= 0.
+    }
+}
+
+// An asteroid has struck a planet
+pub struct PlanetImpactEvent {
+    pub planet: Entity,
+    pub asteroid: Entity,
+    pub energy: f32,
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+// A planet has run out of health
+pub struct PlanetDestroyedEvent {
+    pub planet: Entity,
+}
+
+// A ship has touched a planet
+pub struct PlanetContactEvent {
+    pub planet: Entity,
+    pub ship: Entity,
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+fn planet_impact_update(
+    commands: &mut Commands,
+    mut game_state: ResMut<GameState>,
+
+    sound_bank: Res<SoundBank>,
+    audio: Res<SpatialAudio>,
+
+    mut impact_event_reader: Local<EventReader<PlanetImpactEvent>>,
+    impact_events: Res<Events<PlanetImpactEvent>>,
+    mut expl_events: ResMut<Events<ExplosionEvent>>,
+    mut destroyed_events: ResMut<Events<PlanetDestroyedEvent>>,
+
+    mut planet_query: Query<(&mut Planet, &Transform, &Velocity)>,
+) {
+    if *game_state != GameState::Running {
+        return;
+    }
+
+    let mut impacted_asteroids: Vec<Entity> = Vec::new();
+
+    for event in impact_event_reader.iter(&impact_events) {
+        // An asteroid can touch a planet for several frames before it is despawned
+        if impacted_asteroids.contains(&event.asteroid) {
+            continue;
+        }
+        impacted_asteroids.push(event.asteroid);
+
+        commands.despawn(event.asteroid);
+        expl_events.send(ExplosionEvent::new(event.position, event.velocity));
+        play_sound_at(
+            &sound_bank,
+            &audio,
+            Sound::AsteroidCrash,
+            event.position,
+            event.velocity,
+        );
+
+        let (mut planet, transform, velocity) = match planet_query.get_mut(event.planet) {
+            Ok(planet) => planet,
+            _ => continue,
+        };
+
+        if !planet.destructible() || planet.destroyed() {
+            continue;
+        }
+
+        planet.health -= event.energy * PLANET_IMPACT_DAMAGE_SCALE;
+
+        if planet.destroyed() {
+            destroyed_events.send(PlanetDestroyedEvent {
+                planet: event.planet,
+            });
+
+            if planet.role == PlanetRole::Target {
+                *game_state = GameState::Lost;
+            } else {
+                commands.despawn(event.planet);
+                expl_events.send(ExplosionEvent::new(
+                    transform.translation,
+                    velocity.velocity,
+                ));
+            }
+        }
+    }
+}
+
+fn planet_contact_update(
+    commands: &mut Commands,
+    mut game_state: ResMut<GameState>,
+
+    sound_bank: Res<SoundBank>,
+    audio: Res<SpatialAudio>,
+
+    mut contact_event_reader: Local<EventReader<PlanetContactEvent>>,
+    contact_events: Res<Events<PlanetContactEvent>>,
+    mut expl_events: ResMut<Events<ExplosionEvent>>,
+
+    planet_query: Query<&Planet>,
+) {
+    for event in contact_event_reader.iter(&contact_events) {
+        if *game_state != GameState::Running {
+            return;
+        }
+
+        let planet = match planet_query.get(event.planet) {
+            Ok(planet) => planet,
+            _ => continue,
+        };
+
+        match planet.role {
+            PlanetRole::Target => {
+                println!("YOU WIN!!");
+                *game_state = GameState::Won;
+            }
+            PlanetRole::Hazard => {
+                play_sound_at(
+                    &sound_bank,
+                    &audio,
+                    Sound::PlayerCrash,
+                    event.position,
+                    event.velocity,
+                );
+                commands.despawn(event.ship);
+                expl_events.send(ExplosionEvent::new(event.position, event.velocity));
+                *game_state = GameState::Lost;
+            }
+            PlanetRole::GravityAssist => (),
+        }
+    }
+}
+
+struct PlanetHudMarker;
+
+fn setup_planet_hud(commands: &mut Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.0),
+                    right: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: String::new(),
+                font,
+                style: TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                    alignment: TextAlignment::default(),
+                },
+            },
+            ..Default::default()
+        })
+        .with(PlanetHudMarker);
+}
+
+fn planet_hud_update(
+    planet_query: Query<&Planet>,
+    mut hud_query: Query<(&PlanetHudMarker, &mut Text)>,
+) {
+    let mut text = String::new();
+    for planet in planet_query.iter().filter(|planet| planet.destructible()) {
+        let health_percent = (planet.health / planet.max_health).max(0.) * 100.;
+        text.push_str(&format!(
+            "{} {:.0}%\n",
+            planet.name.to_uppercase(),
+            health_percent
+        ));
+    }
+
+    for (_, mut hud_text) in hud_query.iter_mut() {
+        hud_text.value = text.clone();
+    }
+}
@@ -0,0 +1,328 @@
+use bevy::prelude::*;
+
+use crate::collision::*;
+use crate::input::PlayerInput;
+
+pub struct RadarPlugin;
+
+impl Plugin for RadarPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(RadarSettings::default())
+            .add_startup_system(setup_radar)
+            .add_system(radar_settings_update)
+            .add_system(radar_update);
+    }
+}
+
+pub const RADAR_SIZE: f32 = 200.;
+// Zoom levels, cycled with - and =
+pub const RADAR_RANGES: [f32; 5] = [50., 100., 200., 400., 800.];
+pub const MAX_RADAR_BLIPS: usize = 96;
+
+// How flat the radar disc looks, as if it is seen from above and behind
+const RADAR_TILT: f32 = 0.5;
+const RADAR_BLIP_SIZE: f32 = 5.;
+const RADAR_PLANET_BLIP_SIZE: f32 = 9.;
+
+// F5 to F8 toggle what is shown
+pub struct RadarSettings {
+    pub range_index: usize,
+    pub show_asteroids: bool,
+    pub show_planets: bool,
+    pub show_projectiles: bool,
+    pub show_ships: bool,
+}
+
+impl Default for RadarSettings {
+    fn default() -> Self {
+        RadarSettings {
+            range_index: 2,
+            show_asteroids: true,
+            show_planets: true,
+            show_projectiles: true,
+            show_ships: true,
+        }
+    }
+}
+
+impl RadarSettings {
+    pub fn range(&self) -> f32 {
+        RADAR_RANGES[self.range_index]
+    }
+
+    pub fn shows(&self, etype: EntityType) -> bool {
+        match etype {
+            EntityType::Asteroid => self.show_asteroids,
+            EntityType::Planet => self.show_planets,
+            EntityType::Bullet | EntityType::Mine | EntityType::Missile => self.show_projectiles,
+            EntityType::Alien => self.show_ships,
+            EntityType::Player => false,
+        }
+    }
+}
+
+pub fn radar_color(etype: EntityType) -> Color {
+    match etype {
+        EntityType::Asteroid => Color::rgb(0.6, 0.6, 0.6),
+        EntityType::Planet => Color::rgb(0.3, 0.5, 1.0),
+        EntityType::Bullet => Color::rgb(1.0, 0.2, 0.2),
+        EntityType::Mine => Color::rgb(1.0, 0.5, 0.0),
+        EntityType::Missile => Color::rgb(1.0, 1.0, 0.3),
+        EntityType::Alien => Color::rgb(1.0, 0.2, 1.0),
+        EntityType::Player => Color::WHITE,
+    }
+}
+
+const RADAR_ENTITY_TYPES: [EntityType; 7] = [
+    EntityType::Asteroid,
+    EntityType::Planet,
+    EntityType::Bullet,
+    EntityType::Mine,
+    EntityType::Missile,
+    EntityType::Alien,
+    EntityType::Player,
+];
+
+struct RadarMaterials {
+    blips: Vec<(EntityType, Handle<ColorMaterial>)>,
+}
+
+impl RadarMaterials {
+    fn get(&self, etype: EntityType) -> Option<Handle<ColorMaterial>> {
+        self.blips
+            .iter()
+            .find(|(e, _)| *e == etype)
+            .map(|(_, material)| material.clone())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RadarElement {
+    Blip(usize),
+    // Line from the radar disc up or down to a blip, showing its elevation
+    Stalk(usize),
+}
+
+struct RadarLabel;
+
+fn setup_radar(
+    commands: &mut Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    let radar_materials = RadarMaterials {
+        blips: RADAR_ENTITY_TYPES
+            .iter()
+            .map(|etype| (*etype, materials.add(radar_color(*etype).into())))
+            .collect(),
+    };
+    let stalk_material = materials.add(Color::rgba(0.5, 1.0, 0.5, 0.5).into());
+    let blip_material = radar_materials
+        .get(EntityType::Asteroid)
+        .unwrap_or_default();
+    let player_material = radar_materials.get(EntityType::Player).unwrap_or_default();
+
+    let hidden_style = Style {
+        display: Display::None,
+        position_type: PositionType::Absolute,
+        ..Default::default()
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(RADAR_SIZE), Val::Px(RADAR_SIZE * RADAR_TILT)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(40.0),
+                    right: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.0, 0.2, 0.0, 0.4).into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            // The player, in the middle
+            parent.spawn(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Px(RADAR_BLIP_SIZE), Val::Px(RADAR_BLIP_SIZE)),
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        left: Val::Px((RADAR_SIZE - RADAR_BLIP_SIZE) / 2.),
+                        top: Val::Px((RADAR_SIZE * RADAR_TILT - RADAR_BLIP_SIZE) / 2.),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                material: player_material,
+                ..Default::default()
+            });
+
+            for i in 0..MAX_RADAR_BLIPS {
+                parent
+                    .spawn(NodeBundle {
+                        style: hidden_style.clone(),
+                        material: stalk_material.clone(),
+                        ..Default::default()
+                    })
+                    .with(RadarElement::Stalk(i))
+                    .spawn(NodeBundle {
+                        style: hidden_style.clone(),
+                        material: blip_material.clone(),
+                        ..Default::default()
+                    })
+                    .with(RadarElement::Blip(i));
+            }
+
+            parent
+                .spawn(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            left: Val::Px(4.0),
+                            top: Val::Px(2.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text {
+                        value: String::new(),
+                        font,
+                        style: TextStyle {
+                            font_size: 14.0,
+                            color: Color::rgb(0.5, 1.0, 0.5),
+                            alignment: TextAlignment::default(),
+                        },
+                    },
+                    ..Default::default()
+                })
+                .with(RadarLabel);
+        });
+
+    commands.insert_resource(radar_materials);
+}
+
+fn radar_settings_update(key_input: Res<Input<KeyCode>>, mut settings: ResMut<RadarSettings>) {
+    if key_input.just_pressed(KeyCode::Minus) && settings.range_index > 0 {
+        settings.range_index -= 1;
+    }
+    if key_input.just_pressed(KeyCode::Equals) && settings.range_index + 1 < RADAR_RANGES.len() {
+        settings.range_index += 1;
+    }
+
+    if key_input.just_pressed(KeyCode::F5) {
+        settings.show_asteroids = !settings.show_asteroids;
+    }
+    if key_input.just_pressed(KeyCode::F6) {
+        settings.show_planets = !settings.show_planets;
+    }
+    if key_input.just_pressed(KeyCode::F7) {
+        settings.show_projectiles = !settings.show_projectiles;
+    }
+    if key_input.just_pressed(KeyCode::F8) {
+        settings.show_ships = !settings.show_ships;
+    }
+}
+
+struct RadarBlip {
+    // Where the object is over the radar disc, and where its blip is drawn
+    disc_position: Vec2,
+    blip_position: Vec2,
+    size: f32,
+    etype: EntityType,
+}
+
+fn radar_update(
+    settings: Res<RadarSettings>,
+    radar_materials: Res<RadarMaterials>,
+    player_query: Query<(&PlayerInput, &Transform)>,
+    object_query: Query<(&Transform, &Collision)>,
+    mut element_query: Query<(&RadarElement, &mut Style, &mut Handle<ColorMaterial>)>,
+    mut label_query: Query<(&RadarLabel, &mut Text)>,
+) {
+    let range = settings.range();
+    let scale = RADAR_SIZE / 2. / range;
+    let center = Vec2::new(RADAR_SIZE / 2., RADAR_SIZE * RADAR_TILT / 2.);
+
+    let mut blips: Vec<(f32, RadarBlip)> = Vec::new();
+
+    for (_, player_transform) in player_query.iter() {
+        let inverse_rotation = player_transform.rotation.conjugate();
+
+        for (transform, collision) in object_query.iter() {
+            if !settings.shows(collision.etype) {
+                continue;
+            }
+
+            let displacement = transform.translation - player_transform.translation;
+            let distance = displacement.length();
+            if distance > range {
+                continue;
+            }
+
+            // Relative to the player, who is looking down the negative-z axis
+            let local = inverse_rotation * displacement;
+            let disc_position = center + Vec2::new(local.x, local.z * RADAR_TILT) * scale;
+            let blip_position = disc_position - Vec2::new(0., local.y * RADAR_TILT * scale);
+
+            let size = if collision.etype == EntityType::Planet {
+                RADAR_PLANET_BLIP_SIZE
+            } else {
+                RADAR_BLIP_SIZE
+            };
+
+            blips.push((
+                distance,
+                RadarBlip {
+                    disc_position,
+                    blip_position,
+                    size,
+                    etype: collision.etype,
+                },
+            ));
+        }
+    }
+
+    // Nearest first, in case there are more than there are blips to draw them with
+    blips.sort_by(|(d1, _), (d2, _)| d1.partial_cmp(d2).unwrap());
+
+    for (element, mut style, mut material) in element_query.iter_mut() {
+        let index = match element {
+            RadarElement::Blip(i) | RadarElement::Stalk(i) => *i,
+        };
+        let blip = match blips.get(index) {
+            Some((_, blip)) => blip,
+            None => {
+                style.display = Display::None;
+                continue;
+            }
+        };
+        style.display = Display::Flex;
+
+        match element {
+            RadarElement::Blip(_) => {
+                style.size = Size::new(Val::Px(blip.size), Val::Px(blip.size));
+                style.position.left = Val::Px(blip.blip_position.x - blip.size / 2.);
+                style.position.top = Val::Px(blip.blip_position.y - blip.size / 2.);
+                if let Some(blip_material) = radar_materials.get(blip.etype) {
+                    *material = blip_material;
+                }
+            }
+            RadarElement::Stalk(_) => {
+                let top = blip.disc_position.y.min(blip.blip_position.y);
+                let height = (blip.disc_position.y - blip.blip_position.y).abs();
+                style.size = Size::new(Val::Px(1.), Val::Px(height));
+                style.position.left = Val::Px(blip.disc_position.x);
+                style.position.top = Val::Px(top);
+            }
+        }
+    }
+
+    for (_, mut text) in label_query.iter_mut() {
+        text.value = format!("RANGE {:.0}", range);
+    }
+}
@@ -0,0 +1,224 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::collision::*;
+use crate::data::load_data;
+use crate::difficulty::Difficulty;
+use crate::gravity::*;
+use crate::orbits::*;
+use crate::planets::{Planet, PlanetRole};
+use crate::velocity::*;
+use crate::GameState;
+
+pub const SOLAR_SYSTEM_FILE: &str = "solar_system.ron";
+
+pub struct SolarSystemPlugin;
+
+impl Plugin for SolarSystemPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        // Before the simulation, so rails bodies are where they should be when things collide
+        app.add_system_to_stage(stage::PRE_UPDATE, rails_update);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BodyOrbit {
+    // Name of a body listed before this one
+    pub parent: String,
+    pub elements: KeplerOrbit,
+    // Follow the orbit exactly, rather than being simulated under gravity
+    #[serde(default)]
+    pub on_rails: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BodyDescription {
+    pub name: String,
+    pub role: PlanetRole,
+    pub radius: f32,
+    pub mass: f32,
+    pub color: (f32, f32, f32),
+    // Bodies with no health can't be destroyed
+    #[serde(default)]
+    pub max_health: f32,
+    // Bodies without an orbit are anchored here
+    #[serde(default)]
+    pub position: (f32, f32, f32),
+    #[serde(default)]
+    pub orbit: Option<BodyOrbit>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SolarSystem {
+    pub bodies: Vec<BodyDescription>,
+}
+
+impl SolarSystem {
+    pub fn load(file_name: &str) -> Result<SolarSystem, String> {
+        let solar_system: SolarSystem = load_data(file_name)?;
+
+        // Bodies are spawned in order, so each parent has to come before its satellites
+        for (i, body) in solar_system.bodies.iter().enumerate() {
+            if let Some(orbit) = &body.orbit {
+                let earlier_bodies = &solar_system.bodies[..i];
+                if !earlier_bodies.iter().any(|b| b.name == orbit.parent) {
+                    return Err(format!(
+                        "{} orbits {}, which isn't listed before it in {}",
+                        body.name, orbit.parent, file_name
+                    ));
+                }
+            }
+        }
+
+        Ok(solar_system)
+    }
+}
+
+// Moves a body along a fixed path instead of simulating it
+pub enum Rails {
+    Anchored(Vec3),
+    Orbit { parent: Entity, orbit: KeplerOrbit },
+}
+
+pub struct SpawnedBody {
+    pub name: String,
+    pub entity: Entity,
+    pub role: PlanetRole,
+    pub body: CentralBody,
+}
+
+pub fn spawn_solar_system(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    gravitational_constant: f32,
+    solar_system: &SolarSystem,
+) -> Vec<SpawnedBody> {
+    let mut spawned: Vec<SpawnedBody> = Vec::new();
+
+    for description in solar_system.bodies.iter() {
+        let (position, velocity, rails) = match &description.orbit {
+            Some(orbit) => {
+                // Parents are checked when the solar system is loaded
+                let parent = match spawned.iter().find(|b| b.name == orbit.parent) {
+                    Some(parent) => parent,
+                    None => continue,
+                };
+                let (position, velocity) = orbit
+                    .elements
+                    .state_around(gravitational_constant, &parent.body);
+                let rails = if orbit.on_rails {
+                    Some(Rails::Orbit {
+                        parent: parent.entity,
+                        orbit: orbit.elements,
+                    })
+                } else {
+                    None
+                };
+                (position, velocity, rails)
+            }
+            None => {
+                let (x, y, z) = description.position;
+                let position = Vec3::new(x, y, z);
+                (position, Vec3::zero(), Some(Rails::Anchored(position)))
+            }
+        };
+
+        let (r, g, b) = description.color;
+        let mass = description.mass;
+
+        commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Icosphere {
+                    radius: description.radius,
+                    ..Default::default()
+                })),
+                material: materials.add(Color::rgb(r, g, b).into()),
+                transform: Transform::from_translation(position),
+                ..Default::default()
+            })
+            .with(Planet::new(
+                &description.name,
+                description.role,
+                description.max_health,
+            ))
+            .with(Gravity { mass })
+            .with(Collision {
+                mass,
+                radius: description.radius,
+                etype: EntityType::Planet,
+            })
+            .with(Velocity { velocity });
+
+        if let Some(rails) = rails {
+            commands.with(rails);
+        }
+
+        let entity = match commands.current_entity() {
+            Some(entity) => entity,
+            None => continue,
+        };
+
+        spawned.push(SpawnedBody {
+            name: description.name.clone(),
+            entity,
+            role: description.role,
+            body: CentralBody {
+                position,
+                velocity,
+                mass,
+            },
+        });
+    }
+
+    spawned
+}
+
+fn rails_update(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    difficulty: Res<Difficulty>,
+    mut rails_query: Query<(Entity, &mut Rails)>,
+    mut body_query: Query<(Entity, &mut Transform, &mut Velocity, &Gravity)>,
+) {
+    if *game_state != GameState::Running {
+        return;
+    }
+
+    let gravitational_constant = GRAVITATIONAL_CONSTANT * difficulty.gravity_scale;
+
+    let bodies: Vec<(Entity, CentralBody)> = body_query
+        .iter_mut()
+        .map(|(e, t, v, g)| {
+            (
+                e,
+                CentralBody {
+                    position: t.translation,
+                    velocity: v.velocity,
+                    mass: g.mass,
+                },
+            )
+        })
+        .collect();
+
+    for (entity, mut rails) in rails_query.iter_mut() {
+        let (position, velocity) = match &mut *rails {
+            Rails::Anchored(position) => (*position, Vec3::zero()),
+            Rails::Orbit { parent, orbit } => {
+                let parent = match bodies.iter().find(|(e, _)| *e == *parent) {
+                    Some((_, parent)) => parent,
+                    None => continue,
+                };
+                let gravitational_parameter =
+                    parent.gravitational_parameter(gravitational_constant);
+                *orbit = orbit.advanced(gravitational_parameter, time.delta_seconds);
+                orbit.state_around(gravitational_constant, parent)
+            }
+        };
+
+        if let Ok((_, mut transform, mut body_velocity, _)) = body_query.get_mut(entity) {
+            transform.translation = position;
+            body_velocity.velocity = velocity;
+        }
+    }
+}
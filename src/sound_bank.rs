@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::audio::SoundEffect;
+use crate::data::load_data;
+
+pub const SOUND_BANK_FILE: &str = "sounds.ron";
+
+pub struct SoundBankPlugin {
+    pub description: SoundBankDescription,
+}
+
+impl Plugin for SoundBankPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(self.description.clone())
+            .add_startup_system(setup_sound_bank);
+    }
+}
+
+// Declares the Sound enum along with the list of every sound, so the two can't disagree
+macro_rules! sounds {
+    ($($sound:ident,)*) => {
+        // Every sound the game plays, described in the sound bank data file
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+        pub enum Sound {
+            $($sound,)*
+        }
+
+        const ALL_SOUNDS: &[Sound] = &[$(Sound::$sound,)*];
+    };
+}
+
+sounds! {
+    Laser,
+    LaserShot,
+    MissileLaunch,
+    MineDrop,
+    AsteroidCollision,
+    EnemyExplode,
+    AsteroidCrash,
+    PlayerCrash,
+    Thruster,
+    GameWin,
+    Warning,
+    Soundtrack,
+    BackgroundMusic,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SoundDescription {
+    pub sound: Sound,
+    // Asset paths, one picked at random each time the sound plays
+    pub variants: Vec<String>,
+    #[serde(default)]
+    pub effect: SoundEffect,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SoundBankDescription {
+    pub sounds: Vec<SoundDescription>,
+}
+
+impl SoundBankDescription {
+    pub fn load(file_name: &str) -> Result<SoundBankDescription, String> {
+        let description: SoundBankDescription = load_data(file_name)?;
+
+        for (i, sound) in description.sounds.iter().enumerate() {
+            let name = sound.sound;
+            if sound.variants.is_empty() {
+                return Err(format!("{:?} has no variants in {}", name, file_name));
+            }
+            let listed_before = &description.sounds[..i];
+            if listed_before.iter().any(|s| s.sound == name) {
+                return Err(format!("{:?} is listed twice in {}", name, file_name));
+            }
+        }
+
+        // Every sound has to be there, so playing one can't fail
+        if let Some(missing) = ALL_SOUNDS
+            .iter()
+            .find(|sound| !description.sounds.iter().any(|s| s.sound == **sound))
+        {
+            return Err(format!("{:?} is missing from {}", missing, file_name));
+        }
+
+        Ok(description)
+    }
+}
+
+struct BankedSound {
+    effect: SoundEffect,
+    variants: Vec<Handle<AudioSource>>,
+}
+
+// Holds on to every sound so they are loaded before they first play
+pub struct SoundBank {
+    sounds: HashMap<Sound, BankedSound>,
+}
+
+impl SoundBank {
+    pub fn new(description: &SoundBankDescription, asset_server: &AssetServer) -> SoundBank {
+        let sounds = description
+            .sounds
+            .iter()
+            .map(|sound| {
+                let variants = sound
+                    .variants
+                    .iter()
+                    .map(|variant| asset_server.load(variant.as_str()))
+                    .collect();
+                let banked = BankedSound {
+                    effect: sound.effect,
+                    variants,
+                };
+                (sound.sound, banked)
+            })
+            .collect();
+
+        SoundBank { sounds }
+    }
+
+    // One of the sound's variants, and how to play it
+    pub fn pick(&self, sound: Sound) -> (Handle<AudioSource>, SoundEffect) {
+        let banked = &self.sounds[&sound];
+        let variant = rand::thread_rng().gen_range(0, banked.variants.len());
+        (banked.variants[variant].clone(), banked.effect)
+    }
+}
+
+fn setup_sound_bank(
+    commands: &mut Commands,
+    asset_server: Res<AssetServer>,
+    description: Res<SoundBankDescription>,
+) {
+    commands.insert_resource(SoundBank::new(&description, &asset_server));
+}
@@ -0,0 +1,325 @@
+use bevy::prelude::*;
+
+use crate::collision::*;
+use crate::enemies::Enemy;
+use crate::hud::*;
+use crate::input::{CameraInput, PlayerInput};
+use crate::missiles::Missile;
+use crate::velocity::*;
+use crate::GameState;
+
+pub struct TargetingPlugin;
+
+impl Plugin for TargetingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(TargetLock::default())
+            .add_startup_system(setup_targeting_hud)
+            .add_system(target_lock_update)
+            .add_system(targeting_hud_update);
+    }
+}
+
+// Aliens further than this can't be locked, and locks break a little further out
+pub const TARGET_LOCK_RANGE: f32 = 300.;
+pub const TARGET_LOCK_BREAK_RANGE: f32 = 450.;
+// Threats closer than this get an arrow on the edge of the screen when they are off it
+pub const THREAT_INDICATOR_RANGE: f32 = 250.;
+pub const MAX_THREAT_INDICATORS: usize = 8;
+// Distance of threat arrows from the edge of the window
+const THREAT_INDICATOR_MARGIN: f32 = 40.;
+const TARGET_RETICLE_FONT_SIZE: f32 = 32.;
+
+// The alien the player has locked on to. Its predicted path is drawn by the trajectory
+// system, rather than by adding a component to an alien that may be about to be destroyed
+#[derive(Default)]
+pub struct TargetLock {
+    pub target: Option<Entity>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TargetingHud {
+    Reticle,
+    Info,
+    ThreatIndicator(usize),
+}
+
+pub fn target_class(etype: EntityType) -> &'static str {
+    match etype {
+        EntityType::Alien => "ALIEN FIGHTER",
+        EntityType::Missile => "MISSILE",
+        EntityType::Asteroid => "ASTEROID",
+        EntityType::Planet => "PLANET",
+        _ => "UNKNOWN",
+    }
+}
+
+fn spawn_hud_text(
+    commands: &mut Commands,
+    font: Handle<Font>,
+    style: Style,
+    font_size: f32,
+    color: Color,
+    marker: TargetingHud,
+) {
+    commands
+        .spawn(TextBundle {
+            style,
+            text: Text {
+                value: String::new(),
+                font,
+                style: TextStyle {
+                    font_size,
+                    color,
+                    alignment: TextAlignment::default(),
+                },
+            },
+            ..Default::default()
+        })
+        .with(marker);
+}
+
+fn setup_targeting_hud(commands: &mut Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    spawn_hud_text(
+        commands,
+        font.clone(),
+        Style {
+            position_type: PositionType::Absolute,
+            ..Default::default()
+        },
+        TARGET_RETICLE_FONT_SIZE,
+        Color::rgb(1.0, 0.3, 0.3),
+        TargetingHud::Reticle,
+    );
+
+    spawn_hud_text(
+        commands,
+        font.clone(),
+        Style {
+            align_self: AlignSelf::FlexEnd,
+            position_type: PositionType::Absolute,
+            position: Rect {
+                top: Val::Px(5.0),
+                right: Val::Px(15.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        20.,
+        Color::rgb(1.0, 0.3, 0.3),
+        TargetingHud::Info,
+    );
+
+    for i in 0..MAX_THREAT_INDICATORS {
+        spawn_hud_text(
+            commands,
+            font.clone(),
+            Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            HUD_MARKER_FONT_SIZE,
+            Color::rgb(1.0, 0.6, 0.2),
+            TargetingHud::ThreatIndicator(i),
+        );
+    }
+}
+
+// Q locks the nearest alien, then cycles outwards through the others in range
+fn target_lock_update(
+    key_input: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    mut lock: ResMut<TargetLock>,
+    player_query: Query<(&PlayerInput, &Transform)>,
+    alien_query: Query<(Entity, &Enemy, &Transform)>,
+) {
+    let player_position = match player_query.iter().next() {
+        Some((_, transform)) => transform.translation,
+        None => {
+            lock.target = None;
+            return;
+        }
+    };
+
+    let distance_to = |entity: Entity| {
+        alien_query
+            .get(entity)
+            .ok()
+            .map(|(_, _, t)| (t.translation - player_position).length())
+    };
+
+    // Break the lock on destroyed or distant targets
+    if let Some(target) = lock.target {
+        match distance_to(target) {
+            Some(distance) if distance <= TARGET_LOCK_BREAK_RANGE => (),
+            _ => lock.target = None,
+        }
+    }
+
+    if *game_state == GameState::Running && key_input.just_pressed(KeyCode::Q) {
+        let mut candidates: Vec<(Entity, f32)> = alien_query
+            .iter()
+            .map(|(e, _, t)| (e, (t.translation - player_position).length()))
+            .filter(|(_, distance)| *distance <= TARGET_LOCK_RANGE)
+            .collect();
+        candidates.sort_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap());
+
+        let current_index = lock
+            .target
+            .and_then(|target| candidates.iter().position(|(e, _)| *e == target));
+        lock.target = match current_index {
+            Some(index) => Some(candidates[(index + 1) % candidates.len()].0),
+            None => candidates.first().map(|(e, _)| *e),
+        };
+    }
+}
+
+// Arrow pointing from the middle of the screen in a direction, y down
+fn direction_arrow(direction: Vec2) -> &'static str {
+    const ARROWS: [&str; 8] = ["→", "↘", "↓", "↙", "←", "↖", "↑", "↗"];
+    let angle = direction.y.atan2(direction.x);
+    let octant = (angle / (std::f32::consts::PI / 4.)).round() as i32;
+    ARROWS[octant.rem_euclid(8) as usize]
+}
+
+fn on_screen(screen_position: Vec2, window: &Window) -> bool {
+    screen_position.x >= 0.
+        && screen_position.x <= window.width()
+        && screen_position.y >= 0.
+        && screen_position.y <= window.height()
+}
+
+// Where on the edge of the window to point at something that is off it
+fn edge_position(camera_transform: &GlobalTransform, window: &Window, position: Vec3) -> Vec2 {
+    // Relative to the camera, which looks down the negative-z axis
+    let view_position = camera_transform
+        .compute_matrix()
+        .inverse()
+        .transform_point3(position);
+    let mut direction = Vec2::new(view_position.x, -view_position.y);
+    if direction.length_squared() <= 0. {
+        // Directly behind
+        direction = Vec2::new(0., 1.);
+    }
+
+    let half_extents = Vec2::new(
+        window.width() / 2. - THREAT_INDICATOR_MARGIN,
+        window.height() / 2. - THREAT_INDICATOR_MARGIN,
+    );
+    let scale = (half_extents.x / direction.x.abs()).min(half_extents.y / direction.y.abs());
+    Vec2::new(window.width() / 2., window.height() / 2.) + direction * scale
+}
+
+struct Threat {
+    position: Vec3,
+    distance: f32,
+}
+
+fn targeting_hud_update(
+    windows: Res<Windows>,
+    lock: Res<TargetLock>,
+    camera_query: Query<(&CameraInput, &Camera, &GlobalTransform)>,
+    player_query: Query<(&PlayerInput, &Transform, &Velocity)>,
+    alien_query: Query<(&Enemy, &Transform, &Velocity, &Collision)>,
+    missile_query: Query<(&Missile, &Transform)>,
+    mut hud_query: Query<(&TargetingHud, &mut Style, &mut Text)>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let (camera, camera_transform) = match camera_query.iter().next() {
+        Some((_, camera, transform)) => (camera, transform),
+        None => return,
+    };
+    let (player_position, player_velocity) = match player_query.iter().next() {
+        Some((_, transform, velocity)) => (transform.translation, velocity.velocity),
+        None => {
+            for (_, _, mut text) in hud_query.iter_mut() {
+                text.value = String::new();
+            }
+            return;
+        }
+    };
+
+    let target = lock.target.and_then(|e| alien_query.get(e).ok());
+
+    // Aliens and the missiles hunting the player, nearest first
+    let mut threats: Vec<Threat> = alien_query
+        .iter()
+        .map(|(_, t, _, _)| t.translation)
+        .chain(
+            missile_query
+                .iter()
+                .filter(|(m, _)| m.target_etype == EntityType::Player)
+                .map(|(_, t)| t.translation),
+        )
+        .map(|position| Threat {
+            position,
+            distance: (position - player_position).length(),
+        })
+        .filter(|threat| threat.distance <= THREAT_INDICATOR_RANGE)
+        .filter(
+            |threat| match world_to_screen(camera, camera_transform, window, threat.position) {
+                Some(screen_position) => !on_screen(screen_position, window),
+                None => true,
+            },
+        )
+        .collect();
+    threats.sort_by(|t1, t2| t1.distance.partial_cmp(&t2.distance).unwrap());
+
+    for (hud, mut style, mut text) in hud_query.iter_mut() {
+        match hud {
+            TargetingHud::Reticle => {
+                let screen_position = target.and_then(|(_, t, _, _)| {
+                    world_to_screen(camera, camera_transform, window, t.translation)
+                });
+                match screen_position {
+                    Some(screen_position) => {
+                        place_hud_marker(&mut style, screen_position, TARGET_RETICLE_FONT_SIZE);
+                        text.value = String::from("[   ]");
+                    }
+                    None => text.value = String::new(),
+                }
+            }
+            TargetingHud::Info => {
+                text.value = match target {
+                    Some((_, t, v, c)) => {
+                        let displacement = t.translation - player_position;
+                        let distance = displacement.length();
+                        let relative_velocity = v.velocity - player_velocity;
+                        let closing_speed = if distance > 0. {
+                            -relative_velocity.dot(displacement / distance)
+                        } else {
+                            0.
+                        };
+                        format!(
+                            "TARGET {}\nDIST {:.0}\nREL {:.1} (CLOSING {:.1})",
+                            target_class(c.etype),
+                            distance,
+                            relative_velocity.length(),
+                            closing_speed
+                        )
+                    }
+                    None => String::from("NO TARGET (Q)"),
+                };
+            }
+            TargetingHud::ThreatIndicator(i) => match threats.get(*i) {
+                Some(threat) => {
+                    let edge = edge_position(camera_transform, window, threat.position);
+                    let direction = edge - Vec2::new(window.width() / 2., window.height() / 2.);
+                    place_hud_marker(&mut style, edge, HUD_MARKER_FONT_SIZE);
+                    text.value = format!("{} {:.0}", direction_arrow(direction), threat.distance);
+                    // The nearest threat stands out
+                    text.style.color = if *i == 0 {
+                        Color::rgb(1.0, 0.2, 0.2)
+                    } else {
+                        Color::rgb(1.0, 0.6, 0.2)
+                    };
+                }
+                None => text.value = String::new(),
+            },
+        }
+    }
+}
@@ -1,135 +1,360 @@
-use crate::input::PlayerInput;
-use crate::velocity::Velocity;
-use crate::GameState;
+use std::collections::VecDeque;
+
 use bevy::prelude::*;
+use bevy::render::mesh::Indices;
+use bevy::render::pipeline::PrimitiveTopology;
 use rand::Rng;
 
-pub struct MotionTrailPlugin;
-
-// Settings
-const TRAIL_SIZE: f32 = 0.025;
-const MAX_TRAIL_DIST: f32 = 10.0;
-const SPAWN_ANGLE_RADIUS: f32 = std::f32::consts::PI / 2.0;
-const NUM_OF_TRAIL_PARTICLES: i32 = 40;
-
-// Internal derived constants
-const MAX_TRAIL_DIST_SQ: f32 = MAX_TRAIL_DIST * MAX_TRAIL_DIST;
-const SPAWN_DISTANCE: f32 = MAX_TRAIL_DIST * 0.75;
+use crate::args::parse_f32_flags;
+use crate::input::{CameraInput, PlayerInput};
+use crate::velocity::Velocity;
+use crate::GameState;
 
-struct TrailObj;
+pub struct MotionTrailPlugin {
+    pub settings: MotionTrailSettings,
+}
 
 impl Plugin for MotionTrailPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(setup_trail).add_system(update_trail);
+        app.add_resource(self.settings)
+            .add_resource(SpeedDust::default())
+            .add_startup_system(setup_trail_meshes)
+            .add_system(speed_dust_update)
+            .add_system(ribbon_trail_update)
+            .add_system_to_stage(stage::POST_UPDATE, trail_mesh_update);
     }
-    // span 1 quad, re-draw very 60ms
 }
 
-fn make_trail_obj(
-    commands: &mut Commands,
-    trail_material: &Handle<StandardMaterial>,
-    trail_mesh: &Handle<Mesh>,
-) {
-    commands
-        .spawn(PbrBundle {
-            mesh: trail_mesh.clone(),
-            material: trail_material.clone(),
-            transform: Transform {
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .with(TrailObj);
+// Dust fills a cube this far out from the player on each side, and wraps around as they fly
+const DUST_RANGE: f32 = 15.;
+const MAX_DUST_MOTES: usize = 2000;
+const DUST_WIDTH: f32 = 0.03;
+
+// Ribbons record where their ship was this often, in seconds
+const TRAIL_SAMPLE_INTERVAL: f32 = 0.05;
+pub const SHIP_TRAIL_WIDTH: f32 = 0.5;
+
+// How much dust and how long the streaks and trails are. Each can be changed with
+// e.g. -dust-density=5, and 0 turns it off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MotionTrailSettings {
+    // Motes per thousand cubic units around the player
+    pub dust_density: f32,
+    // Seconds of the player's motion each mote is streaked over
+    pub dust_streak: f32,
+    // Seconds of flight each ribbon trail shows
+    pub trail_length: f32,
+}
+
+impl Default for MotionTrailSettings {
+    fn default() -> Self {
+        MotionTrailSettings {
+            dust_density: 10.,
+            dust_streak: 0.05,
+            trail_length: 1.5,
+        }
+    }
+}
+
+impl MotionTrailSettings {
+    pub fn from_args(args: &[String]) -> MotionTrailSettings {
+        let mut settings = MotionTrailSettings::default();
+
+        parse_f32_flags(
+            args,
+            &mut [
+                ("-dust-density", &mut settings.dust_density),
+                ("-dust-streak", &mut settings.dust_streak),
+                ("-trail-length", &mut settings.trail_length),
+            ],
+        );
+
+        settings
+    }
+
+    fn dust_count(&self) -> usize {
+        let volume = (DUST_RANGE * 2.).powi(3);
+        ((self.dust_density * volume / 1000.) as usize).min(MAX_DUST_MOTES)
+    }
 }
 
-fn setup_trail(
+// Motes hanging still in space, which streak past the player to show how fast they're going
+#[derive(Default)]
+struct SpeedDust {
+    motes: Vec<Vec3>,
+}
+
+// Leaves a ribbon behind a ship, tapering away over the trail length
+pub struct RibbonTrail {
+    pub width: f32,
+    // Newest first, with how many seconds ago the ship was there
+    points: VecDeque<(Vec3, f32)>,
+    since_sample: f32,
+}
+
+impl RibbonTrail {
+    pub fn new(width: f32) -> RibbonTrail {
+        RibbonTrail {
+            width,
+            points: VecDeque::new(),
+            since_sample: TRAIL_SAMPLE_INTERVAL,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TrailMeshKind {
+    Dust,
+    Ribbons,
+}
+
+struct TrailMesh {
+    kind: TrailMeshKind,
+    mesh: Handle<Mesh>,
+}
+
+fn trail_mesh(positions: Vec<[f32; 3]>, indices: Vec<u32>, normal: Vec3) -> Mesh {
+    let normals: Vec<[f32; 3]> = vec![normal.into(); positions.len()];
+    let uvs: Vec<[f32; 2]> = vec![[0., 0.]; positions.len()];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn setup_trail_meshes(
     commands: &mut Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    let trail_material = materials.add(Color::rgb(0.75, 0.75, 1.0).into());
-    let trail_mesh = meshes.add(Mesh::from(shape::Cube { size: TRAIL_SIZE }));
+    let trails = [
+        (TrailMeshKind::Dust, Color::rgba(0.75, 0.75, 1.0, 0.6)),
+        (TrailMeshKind::Ribbons, Color::rgba(0.6, 0.8, 1.0, 0.3)),
+    ];
+    for (kind, color) in trails.iter() {
+        let mesh = meshes.add(trail_mesh(Vec::new(), Vec::new(), Vec3::unit_z()));
 
-    for _ in 1..NUM_OF_TRAIL_PARTICLES {
-        make_trail_obj(commands, &trail_material, &trail_mesh);
+        commands
+            .spawn(PbrBundle {
+                mesh: mesh.clone(),
+                material: materials.add(StandardMaterial {
+                    albedo: *color,
+                    shaded: false,
+                    ..Default::default()
+                }),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: true,
+                },
+                ..Default::default()
+            })
+            .with(TrailMesh { kind: *kind, mesh });
     }
 }
 
-fn build_quat_to_align_vec(original_vector: Vec3, new_direction: Vec3) -> Quat {
-    let quat_vector = original_vector.cross(new_direction).normalize();
-    let quat_angle = new_direction.x.acos();
-    Quat::from_axis_angle(quat_vector, quat_angle)
-}
+fn speed_dust_update(
+    settings: Res<MotionTrailSettings>,
+    mut dust: ResMut<SpeedDust>,
+    player_query: Query<(&PlayerInput, &Transform)>,
+) {
+    let player_position = match player_query.iter().next() {
+        Some((_, transform)) => transform.translation,
+        None => return,
+    };
 
-fn build_trail_quat(velocity: &Vec3) -> Quat {
-    let velocity_copy = velocity.clone().normalize();
-    build_quat_to_align_vec(Vec3::unit_x(), velocity_copy)
+    let count = settings.dust_count();
+    let mut rng = rand::thread_rng();
+    dust.motes.truncate(count);
+    while dust.motes.len() < count {
+        let offset = Vec3::new(
+            rng.gen_range(-DUST_RANGE, DUST_RANGE),
+            rng.gen_range(-DUST_RANGE, DUST_RANGE),
+            rng.gen_range(-DUST_RANGE, DUST_RANGE),
+        );
+        dust.motes.push(player_position + offset);
+    }
+
+    // Motes left behind come back in on the far side, so the dust always surrounds the player
+    let wrap = |mote: f32, centre: f32| {
+        centre + (mote - centre + DUST_RANGE).rem_euclid(DUST_RANGE * 2.) - DUST_RANGE
+    };
+    for mote in dust.motes.iter_mut() {
+        *mote = Vec3::new(
+            wrap(mote.x, player_position.x),
+            wrap(mote.y, player_position.y),
+            wrap(mote.z, player_position.z),
+        );
+    }
 }
 
-fn valid_vec3(vec: &Vec3) -> bool {
-    !vec.is_nan().any()
+fn ribbon_trail_update(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    settings: Res<MotionTrailSettings>,
+    mut trail_query: Query<(&mut RibbonTrail, &Transform)>,
+) {
+    // Trails hang in place while paused
+    if *game_state == GameState::Paused {
+        return;
+    }
+
+    let dt = time.delta_seconds;
+    for (mut trail, transform) in trail_query.iter_mut() {
+        for point in trail.points.iter_mut() {
+            point.1 += dt;
+        }
+        while let Some((_, age)) = trail.points.back() {
+            if *age < settings.trail_length {
+                break;
+            }
+            trail.points.pop_back();
+        }
+
+        trail.since_sample += dt;
+        if trail.since_sample >= TRAIL_SAMPLE_INTERVAL && settings.trail_length > 0. {
+            trail.since_sample = 0.;
+            trail.points.push_front((transform.translation, 0.));
+        }
+    }
 }
 
-#[allow(dead_code)]
-fn calc_trail_spawn_point(
-    player_position: Vec3,
-    player_up: Vec3,
-    player_velocity_unit: Vec3,
-) -> Vec3 {
-    let mut rng = rand::thread_rng();
-    let projected_radius = SPAWN_DISTANCE * (SPAWN_ANGLE_RADIUS / 2.0).sin();
-    let projected_dir = if valid_vec3(&player_velocity_unit) {
-        player_velocity_unit
-    } else {
-        Vec3::zero()
-    };
-    let x_mag = rng.gen_range(-1.0, 1.0);
-    let y_mag = rng.gen_range(-1.0, 1.0);
-    let x_dir = player_up.cross(projected_dir).normalize();
-    let y_dir = x_dir.cross(projected_dir).normalize();
+// Adds a quad from the start to the end of a line, turned to face the camera
+fn push_line(
+    positions: &mut Vec<[f32; 3]>,
+    indices: &mut Vec<u32>,
+    start: Vec3,
+    end: Vec3,
+    width: f32,
+    camera_position: Vec3,
+) {
+    let along = end - start;
+    let side = along.cross(camera_position - start);
+    if side.length_squared() <= 0. {
+        return;
+    }
+    let half_side = side.normalize() * width / 2.;
 
-    let final_pos = player_position
-        + projected_dir * MAX_TRAIL_DIST * 0.75
-        + x_mag * x_dir * projected_radius
-        + y_mag * y_dir * projected_radius;
-    final_pos
+    let v = positions.len() as u32;
+    positions.push((start - half_side).into());
+    positions.push((start + half_side).into());
+    positions.push((end + half_side).into());
+    positions.push((end - half_side).into());
+    indices.extend_from_slice(&[v, v + 1, v + 2, v, v + 2, v + 3]);
 }
 
-fn update_trail(
-    mut trail_query: Query<(&TrailObj, &mut Transform)>,
-    player_query: Query<(&PlayerInput, &Transform, &Velocity)>,
-    game_state: Res<GameState>,
+// Adds a strip through the points, narrowing from the given width at the head to nothing
+fn push_ribbon(
+    positions: &mut Vec<[f32; 3]>,
+    indices: &mut Vec<u32>,
+    points: &[Vec3],
+    widths: &[f32],
+    camera_position: Vec3,
 ) {
-    if *game_state == GameState::Lost {
-        for (_, mut transform) in trail_query.iter_mut() {
-            transform.scale = Vec3::one();
+    let first = positions.len() as u32;
+    let mut added = 0;
+    for (i, point) in points.iter().enumerate() {
+        // Each point is turned along the ribbon as it passes through, so the joins don't gap
+        let previous = points[i.saturating_sub(1)];
+        let next = points[(i + 1).min(points.len() - 1)];
+        let side = (next - previous).cross(camera_position - *point);
+        if side.length_squared() <= 0. {
+            continue;
         }
+        let half_side = side.normalize() * widths[i] / 2.;
+
+        positions.push((*point - half_side).into());
+        positions.push((*point + half_side).into());
+        added += 1;
+    }
+
+    for i in 1..added {
+        let v = first + (i - 1) * 2;
+        indices.extend_from_slice(&[v, v + 1, v + 3, v, v + 3, v + 2]);
     }
+}
 
-    for (_, player_transform, velocity_info) in player_query.iter() {
-        let player_up = player_transform.rotation * Vec3::unit_y();
-        let player_velocity = velocity_info.velocity.length();
-        let player_velocity_dir = velocity_info.velocity.clone().normalize();
+fn trail_mesh_update(
+    settings: Res<MotionTrailSettings>,
+    dust: Res<SpeedDust>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    camera_query: Query<(&CameraInput, &Transform)>,
+    player_query: Query<(&PlayerInput, &Velocity)>,
+    trail_query: Query<(&RibbonTrail, &Transform)>,
+    mut trail_mesh_query: Query<(&TrailMesh, &mut Visible)>,
+) {
+    let (camera_position, normal) = match camera_query.iter().next() {
+        Some((_, transform)) => (transform.translation, transform.rotation * Vec3::unit_z()),
+        None => return,
+    };
 
-        if !valid_vec3(&player_velocity_dir) {
-            return;
+    let mut dust_positions = Vec::new();
+    let mut dust_indices = Vec::new();
+    if let Some((_, velocity)) = player_query.iter().next() {
+        // Relative to the player the dust flies backwards, so each streak reaches forward to
+        // where the mote was a moment ago
+        let streak = velocity.velocity * settings.dust_streak;
+        for mote in dust.motes.iter() {
+            push_line(
+                &mut dust_positions,
+                &mut dust_indices,
+                *mote,
+                *mote + streak,
+                DUST_WIDTH,
+                camera_position,
+            );
         }
+    }
 
-        let trail_quat = build_trail_quat(&velocity_info.velocity);
-        for (_, mut transform) in trail_query.iter_mut() {
-            let vector_from_player = transform.translation - player_transform.translation;
-
-            let new_translation = if vector_from_player.length_squared() > MAX_TRAIL_DIST_SQ {
-                calc_trail_spawn_point(player_transform.translation, player_up, player_velocity_dir)
-            } else {
-                transform.translation
-            };
-
-            *transform = Transform {
-                translation: new_translation,
-                rotation: trail_quat,
-                scale: Vec3::new(player_velocity, 1.0, 1.0),
-            };
+    let mut ribbon_positions = Vec::new();
+    let mut ribbon_indices = Vec::new();
+    for (trail, transform) in trail_query.iter() {
+        if trail.points.is_empty() || settings.trail_length <= 0. {
+            continue;
+        }
+        // The ribbon runs from the ship itself back through its recorded positions
+        let points: Vec<Vec3> = std::iter::once(transform.translation)
+            .chain(trail.points.iter().map(|(position, _)| *position))
+            .collect();
+        let widths: Vec<f32> = std::iter::once(trail.width)
+            .chain(
+                trail
+                    .points
+                    .iter()
+                    .map(|(_, age)| trail.width * (1. - age / settings.trail_length).max(0.)),
+            )
+            .collect();
+        push_ribbon(
+            &mut ribbon_positions,
+            &mut ribbon_indices,
+            &points,
+            &widths,
+            camera_position,
+        );
+    }
+
+    for (trail_mesh_entity, mut visible) in trail_mesh_query.iter_mut() {
+        let (positions, indices) = match trail_mesh_entity.kind {
+            TrailMeshKind::Dust => (
+                std::mem::take(&mut dust_positions),
+                std::mem::take(&mut dust_indices),
+            ),
+            TrailMeshKind::Ribbons => (
+                std::mem::take(&mut ribbon_positions),
+                std::mem::take(&mut ribbon_indices),
+            ),
+        };
+        let was_visible = visible.is_visible;
+        visible.is_visible = !indices.is_empty();
+
+        // Empty meshes are left alone, rather than rebuilt every frame
+        if !visible.is_visible && !was_visible {
+            continue;
+        }
+        if let Some(mesh) = meshes.get_mut(&trail_mesh_entity.mesh) {
+            *mesh = trail_mesh(positions, indices, normal);
         }
     }
 }
@@ -0,0 +1,361 @@
+use bevy::prelude::*;
+use bevy::render::pipeline::PrimitiveTopology;
+
+use crate::collision::*;
+use crate::difficulty::Difficulty;
+use crate::gravity::*;
+use crate::planets::Planet;
+use crate::solar_system::Rails;
+use crate::targeting::TargetLock;
+use crate::velocity::*;
+
+pub struct TrajectoryPlugin;
+
+impl Plugin for TrajectoryPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(TrajectorySettings::default())
+            .add_system(trajectory_toggle_update)
+            .add_system(trajectory_update);
+    }
+}
+
+// How far ahead paths are predicted, in seconds
+pub const TRAJECTORY_PREDICTION_DURATION: f32 = 10.;
+pub const TRAJECTORY_TIME_STEP: f32 = 1. / 30.;
+pub const TRAJECTORY_MARKER_RADIUS: f32 = 0.6;
+
+pub struct TrajectorySettings {
+    pub enabled: bool,
+}
+
+impl Default for TrajectorySettings {
+    fn default() -> Self {
+        TrajectorySettings { enabled: true }
+    }
+}
+
+// Entities with this get their predicted path drawn ahead of them
+pub struct PredictTrajectory {
+    pub color: Color,
+}
+
+struct TrajectoryLine {
+    owner: Entity,
+    mesh: Handle<Mesh>,
+}
+
+// Where a predicted path runs into something
+struct TrajectoryMarker {
+    owner: Entity,
+}
+
+#[derive(Clone, Copy)]
+pub struct PredictedBody {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub mass: f32,
+    pub radius: f32,
+    // Only the gravity of attractors moves other bodies, to keep prediction cheap
+    pub attractor: bool,
+    // Held in place, like bodies anchored on rails
+    pub anchored: bool,
+}
+
+pub struct PredictedPath {
+    pub points: Vec<Vec3>,
+    // The body the path runs into and where
+    pub impact: Option<(Entity, Vec3)>,
+}
+
+fn gravitational_acceleration(
+    gravitational_constant: f32,
+    position: Vec3,
+    attractor_position: Vec3,
+    attractor_mass: f32,
+) -> Vec3 {
+    let displacement = attractor_position - position;
+    let dist_squared = displacement.length_squared();
+    if dist_squared < MIN_GRAVITATION_DISTANCE_SQUARED {
+        return Vec3::zero();
+    }
+    displacement * (gravitational_constant * attractor_mass / (dist_squared * dist_squared.sqrt()))
+}
+
+// Forward-integrates the same gravity model as gravity_update, stopping at the first body
+// the subject would hit
+pub fn predict_trajectory(
+    subject: &PredictedBody,
+    bodies: &[PredictedBody],
+    gravitational_constant: f32,
+    duration: f32,
+    time_step: f32,
+) -> PredictedPath {
+    let mut subject = *subject;
+    let mut bodies: Vec<PredictedBody> = bodies
+        .iter()
+        .filter(|b| b.entity != subject.entity)
+        .copied()
+        .collect();
+
+    let step_count = (duration / time_step).ceil() as usize;
+    let mut points = Vec::with_capacity(step_count + 1);
+    points.push(subject.position);
+
+    for _ in 0..step_count {
+        let accelerations: Vec<Vec3> = bodies
+            .iter()
+            .map(|body| {
+                if body.anchored {
+                    return Vec3::zero();
+                }
+                bodies
+                    .iter()
+                    .filter(|a| a.attractor && a.entity != body.entity)
+                    .fold(Vec3::zero(), |sum, a| {
+                        sum + gravitational_acceleration(
+                            gravitational_constant,
+                            body.position,
+                            a.position,
+                            a.mass,
+                        )
+                    })
+            })
+            .collect();
+
+        let subject_acceleration = bodies.iter().fold(Vec3::zero(), |sum, b| {
+            sum + gravitational_acceleration(
+                gravitational_constant,
+                subject.position,
+                b.position,
+                b.mass,
+            )
+        });
+
+        for (body, acceleration) in bodies.iter_mut().zip(accelerations.iter()) {
+            body.velocity += *acceleration * time_step;
+            body.position += body.velocity * time_step;
+        }
+        subject.velocity += subject_acceleration * time_step;
+        subject.position += subject.velocity * time_step;
+
+        let impact = bodies
+            .iter()
+            .filter_map(|body| {
+                swept_sphere_time_of_impact(
+                    subject.position - body.position,
+                    subject.velocity - body.velocity,
+                    subject.radius + body.radius,
+                    time_step,
+                )
+                .map(|time_of_impact| (body, time_of_impact))
+            })
+            .min_by(|(_, t1), (_, t2)| t1.partial_cmp(t2).unwrap());
+
+        if let Some((body, time_of_impact)) = impact {
+            let impact_position =
+                subject.position - subject.velocity * (time_step - time_of_impact);
+            points.push(impact_position);
+            return PredictedPath {
+                points,
+                impact: Some((body.entity, impact_position)),
+            };
+        }
+
+        points.push(subject.position);
+    }
+
+    PredictedPath {
+        points,
+        impact: None,
+    }
+}
+
+fn line_mesh(points: &[Vec3]) -> Mesh {
+    let positions: Vec<[f32; 3]> = points.iter().map(|p| (*p).into()).collect();
+    let normals = vec![[0., 1., 0.]; points.len()];
+    let uvs = vec![[0., 0.]; points.len()];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::LineStrip);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh
+}
+
+fn spawn_trajectory_line(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    owner: Entity,
+    color: Color,
+) {
+    let mesh = meshes.add(line_mesh(&[]));
+
+    commands
+        .spawn(PbrBundle {
+            mesh: mesh.clone(),
+            material: materials.add(StandardMaterial {
+                albedo: color,
+                shaded: false,
+                ..Default::default()
+            }),
+            visible: Visible {
+                is_visible: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with(TrajectoryLine { owner, mesh })
+        .spawn(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Icosphere {
+                radius: TRAJECTORY_MARKER_RADIUS,
+                subdivisions: 2,
+            })),
+            material: materials.add(StandardMaterial {
+                albedo: Color::rgb(1.0, 0.2, 0.2),
+                shaded: false,
+                ..Default::default()
+            }),
+            visible: Visible {
+                is_visible: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with(TrajectoryMarker { owner });
+}
+
+fn trajectory_toggle_update(
+    key_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<TrajectorySettings>,
+) {
+    if key_input.just_pressed(KeyCode::V) {
+        settings.enabled = !settings.enabled;
+    }
+}
+
+fn trajectory_update(
+    commands: &mut Commands,
+    settings: Res<TrajectorySettings>,
+    difficulty: Res<Difficulty>,
+    lock: Res<TargetLock>,
+
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+
+    predictor_query: Query<(Entity, &PredictTrajectory)>,
+    body_query: Query<
+        (
+            Entity,
+            &Transform,
+            &Velocity,
+            Option<&Gravity>,
+            &Collision,
+            Option<&Planet>,
+            Option<&Rails>,
+        ),
+        Without<TrajectoryMarker>,
+    >,
+    mut line_query: Query<(Entity, &TrajectoryLine, &mut Visible), Without<TrajectoryMarker>>,
+    mut marker_query: Query<(Entity, &TrajectoryMarker, &mut Transform, &mut Visible)>,
+) {
+    let gravitational_constant = GRAVITATIONAL_CONSTANT * difficulty.gravity_scale;
+
+    let predicted_body = |(e, t, v, g, c, planet, rails): (
+        Entity,
+        &Transform,
+        &Velocity,
+        Option<&Gravity>,
+        &Collision,
+        Option<&Planet>,
+        Option<&Rails>,
+    )| PredictedBody {
+        entity: e,
+        position: t.translation,
+        velocity: v.velocity,
+        // Ships without gravity are still pulled by everything else
+        mass: g.map_or(0., |g| g.mass),
+        radius: c.radius,
+        attractor: planet.is_some(),
+        anchored: matches!(rails, Some(Rails::Anchored(_))),
+    };
+
+    // Paths only run into and are pulled by the things worth steering around
+    let bodies: Vec<PredictedBody> = body_query
+        .iter()
+        .filter(|(_, _, _, _, c, _, _)| {
+            c.etype == EntityType::Asteroid || c.etype == EntityType::Planet
+        })
+        .map(predicted_body)
+        .collect();
+
+    // The locked target's path is drawn too, until it's destroyed or the lock breaks
+    let mut predictors: Vec<(Entity, Color)> = predictor_query
+        .iter()
+        .map(|(e, predictor)| (e, predictor.color))
+        .collect();
+    if let Some(target) = lock.target.filter(|target| body_query.get(*target).is_ok()) {
+        predictors.push((target, Color::rgb(1.0, 0.4, 0.3)));
+    }
+    let is_predictor = |entity: Entity| predictors.iter().any(|(e, _)| *e == entity);
+
+    // Start drawing paths for new predictors
+    for (owner, color) in predictors.iter() {
+        if !line_query
+            .iter_mut()
+            .any(|(_, line, _)| line.owner == *owner)
+        {
+            spawn_trajectory_line(commands, &mut meshes, &mut materials, *owner, *color);
+        }
+    }
+
+    let mut impacts: Vec<(Entity, Vec3)> = Vec::new();
+
+    for (line_entity, line, mut visible) in line_query.iter_mut() {
+        if !is_predictor(line.owner) {
+            commands.despawn(line_entity);
+            continue;
+        }
+
+        let subject = match body_query.get(line.owner) {
+            Ok(subject) if settings.enabled => predicted_body(subject),
+            _ => {
+                visible.is_visible = false;
+                continue;
+            }
+        };
+
+        let path = predict_trajectory(
+            &subject,
+            &bodies,
+            gravitational_constant,
+            TRAJECTORY_PREDICTION_DURATION,
+            TRAJECTORY_TIME_STEP,
+        );
+
+        visible.is_visible = path.points.len() >= 2;
+        if let Some(mesh) = meshes.get_mut(&line.mesh) {
+            *mesh = line_mesh(&path.points);
+        }
+
+        if let Some((_, impact_position)) = path.impact {
+            impacts.push((line.owner, impact_position));
+        }
+    }
+
+    for (marker_entity, marker, mut transform, mut visible) in marker_query.iter_mut() {
+        if !is_predictor(marker.owner) {
+            commands.despawn(marker_entity);
+            continue;
+        }
+
+        match impacts.iter().find(|(owner, _)| *owner == marker.owner) {
+            Some((_, impact_position)) => {
+                transform.translation = *impact_position;
+                visible.is_visible = true;
+            }
+            None => visible.is_visible = false,
+        }
+    }
+}
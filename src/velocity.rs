@@ -27,3 +27,29 @@ pub fn velocity_update(
         transform.translation += displacement;
     }
 }
+
+// Spin about an axis, with the rate in radians per second as its length
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct AngularVelocity {
+    pub angular_velocity: Vec3,
+}
+
+pub fn angular_velocity_update(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    mut query: Query<(&mut Transform, &AngularVelocity)>,
+) {
+    if *game_state != GameState::Running {
+        return;
+    }
+
+    for (mut transform, angular_velocity) in query.iter_mut() {
+        let rate = angular_velocity.angular_velocity.length();
+        if rate <= 0. {
+            continue;
+        }
+        let axis = angular_velocity.angular_velocity / rate;
+        let spin = Quat::from_axis_angle(axis, rate * time.delta_seconds);
+        transform.rotation = (spin * transform.rotation).normalize();
+    }
+}
@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::difficulty::Difficulty;
+use crate::enemies::*;
+use crate::input::*;
+use crate::planets::{Planet, PlanetRole};
+use crate::GameState;
+
+// Waves are released on a timer, or early once the previous wave has been cleared
+pub const FIRST_WAVE_DELAY: f32 = 3.;
+pub const WAVE_INTERVAL: f32 = 45.;
+pub const WAVE_CLEARED_DELAY: f32 = 5.;
+
+// Wave composition
+pub const WAVE_BASE_ENEMY_COUNT: f32 = 6.;
+pub const WAVE_ENEMY_COUNT_PER_WAVE: f32 = 4.;
+// How much bigger waves get as the player closes in on the target planet
+pub const WAVE_PROGRESS_SCALE: f32 = 1.5;
+pub const WAVE_MIN_SWARM_SIZE: i32 = 2;
+pub const WAVE_MAX_SWARM_SIZE: i32 = 10;
+pub const MAX_ALIVE_ENEMIES: i32 = 100;
+
+// Swarms appear behind the player, far enough away to be off-screen
+pub const WAVE_SPAWN_DISTANCE: f32 = 100.;
+pub const WAVE_SPAWN_DISTANCE_VARIANCE: f32 = 30.;
+pub const WAVE_SWARM_APPROACH_SPEED: f32 = 6.;
+
+#[derive(Default)]
+pub struct WaveDirector {
+    pub wave_number: u32,
+
+    // Seconds the game has been running, excluding time spent paused
+    pub mission_time: f32,
+    pub next_wave_time: f32,
+
+    initial_target_distance: Option<f32>,
+}
+
+impl WaveDirector {
+    pub fn new() -> WaveDirector {
+        WaveDirector {
+            next_wave_time: FIRST_WAVE_DELAY,
+            ..Default::default()
+        }
+    }
+
+    // 0 at the start of the mission, approaching 1 as the player reaches the target planet
+    fn progress(&mut self, target_distance: f32) -> f32 {
+        let initial_distance = *self.initial_target_distance.get_or_insert(target_distance);
+        if initial_distance <= 0. {
+            return 0.;
+        }
+        (1. - target_distance / initial_distance).max(0.).min(1.)
+    }
+
+    fn wave_enemy_count(&self, progress: f32, difficulty: &Difficulty) -> i32 {
+        let base_count =
+            WAVE_BASE_ENEMY_COUNT + WAVE_ENEMY_COUNT_PER_WAVE * (self.wave_number - 1) as f32;
+        let progress_scale = 1. + WAVE_PROGRESS_SCALE * progress;
+        (base_count * progress_scale * difficulty.enemy_count_scale) as i32
+    }
+}
+
+// Pick a point away from where the player is looking
+fn off_screen_spawn_position(player_position: Vec3, player_facing: Vec3) -> Vec3 {
+    let mut rng = rand::thread_rng();
+
+    let mut direction = Vec3::new(
+        rng.gen_range(-1.0, 1.0),
+        rng.gen_range(-1.0, 1.0),
+        rng.gen_range(-1.0, 1.0),
+    )
+    .normalize();
+
+    // Reflect directions in front of the player to behind them
+    let facing_amount = direction.dot(player_facing);
+    if facing_amount > 0. {
+        direction -= 2. * facing_amount * player_facing;
+    }
+
+    let distance = WAVE_SPAWN_DISTANCE + rng.gen_range(0., WAVE_SPAWN_DISTANCE_VARIANCE);
+    player_position + direction * distance
+}
+
+pub fn wave_director_update(
+    commands: &mut Commands,
+    asset_server: Res<AssetServer>,
+
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    difficulty: Res<Difficulty>,
+    mut director: ResMut<WaveDirector>,
+
+    enemy_query: Query<&Enemy>,
+    player_query: Query<(&PlayerInput, &Transform)>,
+    planet_query: Query<(&Planet, &Transform)>,
+) {
+    if *game_state != GameState::Running {
+        return;
+    }
+
+    director.mission_time += time.delta_seconds;
+
+    let alive_enemy_count = enemy_query.iter().count() as i32;
+
+    // Give the player a breather after clearing a wave, then bring the next one forward
+    if director.wave_number > 0 && alive_enemy_count == 0 {
+        let cleared_wave_time = director.mission_time + WAVE_CLEARED_DELAY;
+        if cleared_wave_time < director.next_wave_time {
+            director.next_wave_time = cleared_wave_time;
+        }
+    }
+
+    if director.mission_time < director.next_wave_time {
+        return;
+    }
+
+    for (_, player_transform) in player_query.iter() {
+        let player_position = player_transform.translation;
+
+        // player is looking down the negative-z axis
+        let player_facing = -Mat3::from_quat(player_transform.rotation).z_axis;
+
+        let target_distance = planet_query
+            .iter()
+            .filter(|(p, _)| p.role == PlanetRole::Target)
+            .map(|(_, t)| (t.translation - player_position).length())
+            .fold(f32::MAX, f32::min);
+        let progress = if target_distance < f32::MAX {
+            director.progress(target_distance)
+        } else {
+            0.
+        };
+
+        director.wave_number += 1;
+        director.next_wave_time = director.mission_time + WAVE_INTERVAL;
+
+        let mut remaining_count = director
+            .wave_enemy_count(progress, &difficulty)
+            .min(MAX_ALIVE_ENEMIES - alive_enemy_count);
+
+        let mut rng = rand::thread_rng();
+        while remaining_count > 0 {
+            let swarm_count = rng
+                .gen_range(WAVE_MIN_SWARM_SIZE, WAVE_MAX_SWARM_SIZE)
+                .min(remaining_count);
+            remaining_count -= swarm_count;
+
+            let swarm_position = off_screen_spawn_position(player_position, player_facing);
+            let swarm_velocity =
+                (player_position - swarm_position).normalize() * WAVE_SWARM_APPROACH_SPEED;
+
+            spawn_swarm(
+                commands,
+                &asset_server,
+                swarm_position,
+                swarm_velocity,
+                swarm_count,
+            );
+        }
+    }
+}
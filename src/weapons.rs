@@ -0,0 +1,476 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+
+use crate::audio::*;
+use crate::bullets::*;
+use crate::cooldown::*;
+use crate::input::PlayerInput;
+use crate::missiles::*;
+use crate::EntityType;
+use crate::GameState;
+
+pub struct WeaponsPlugin;
+
+impl Plugin for WeaponsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_weapon_hud)
+            .add_system(weapon_select_update)
+            .add_system(weapon_energy_update)
+            .add_system(weapon_hud_update);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeaponKind {
+    Laser,
+    Spread,
+    Railgun,
+    Missile,
+    Mine,
+}
+
+// In the order of the number keys that select them
+pub const WEAPON_KINDS: [WeaponKind; 5] = [
+    WeaponKind::Laser,
+    WeaponKind::Spread,
+    WeaponKind::Railgun,
+    WeaponKind::Missile,
+    WeaponKind::Mine,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeaponTrigger {
+    // Fires for as long as the button is held
+    Automatic,
+    // Fires once per click
+    SemiAutomatic,
+    // Charges while the button is held, fires on release
+    Charged,
+}
+
+#[derive(Clone, Copy)]
+pub struct WeaponParameters {
+    pub name: &'static str,
+    pub trigger: WeaponTrigger,
+    pub cooldown: f64,
+    pub projectile: ProjectileParameters,
+    pub projectile_count: u32,
+    // Angle in radians between the outermost projectiles of a spread
+    pub spread_angle: f32,
+    pub energy_cost: f32,
+    // Weapons with ammo don't use energy
+    pub max_ammo: Option<u32>,
+    // Fires out of the back of the ship
+    pub rear_mounted: bool,
+    pub sound: Sound,
+    // Camera shake trauma from firing, scaled by charge
+    pub recoil: f32,
+}
+
+pub const RAILGUN_FULL_CHARGE_DURATION: f64 = 1.5;
+pub const RAILGUN_MIN_CHARGE: f32 = 0.2;
+
+pub const MINE_DROP_SPEED: f32 = 5.;
+pub const MINE_LIFETIME: f32 = 30.;
+
+pub fn weapon_parameters(kind: WeaponKind) -> WeaponParameters {
+    match kind {
+        WeaponKind::Laser => WeaponParameters {
+            name: "LASER",
+            trigger: WeaponTrigger::Automatic,
+            cooldown: 0.15,
+            projectile: bullet_parameters(),
+            projectile_count: 1,
+            spread_angle: 0.,
+            energy_cost: 4.,
+            max_ammo: None,
+            rear_mounted: false,
+            sound: Sound::Laser,
+            recoil: 0.05,
+        },
+        WeaponKind::Spread => WeaponParameters {
+            name: "SPREAD",
+            trigger: WeaponTrigger::SemiAutomatic,
+            cooldown: 0.8,
+            projectile: ProjectileParameters {
+                speed: 90.,
+                color: Color::rgb(1.0, 0.6, 0.2),
+                ..bullet_parameters()
+            },
+            projectile_count: 5,
+            spread_angle: 0.25,
+            energy_cost: 12.,
+            max_ammo: None,
+            rear_mounted: false,
+            sound: Sound::LaserShot,
+            recoil: 0.2,
+        },
+        WeaponKind::Railgun => WeaponParameters {
+            name: "RAILGUN",
+            trigger: WeaponTrigger::Charged,
+            cooldown: 1.,
+            projectile: ProjectileParameters {
+                speed: 400.,
+                radius: 0.25,
+                mass: 0.01,
+                color: Color::rgb(0.4, 0.8, 1.0),
+                etype: EntityType::Bullet,
+                lifetime: BULLET_LIFETIME,
+                range: 2. * BULLET_RANGE,
+            },
+            projectile_count: 1,
+            spread_angle: 0.,
+            energy_cost: 35.,
+            max_ammo: None,
+            rear_mounted: false,
+            sound: Sound::LaserShot,
+            recoil: 0.6,
+        },
+        WeaponKind::Missile => WeaponParameters {
+            name: "MISSILE",
+            trigger: WeaponTrigger::SemiAutomatic,
+            cooldown: 1.2,
+            projectile: missile_parameters(),
+            projectile_count: 1,
+            spread_angle: 0.,
+            energy_cost: 0.,
+            max_ammo: Some(8),
+            rear_mounted: false,
+            sound: Sound::MissileLaunch,
+            recoil: 0.15,
+        },
+        WeaponKind::Mine => WeaponParameters {
+            name: "MINE",
+            trigger: WeaponTrigger::SemiAutomatic,
+            cooldown: 1.,
+            projectile: ProjectileParameters {
+                speed: MINE_DROP_SPEED,
+                radius: 0.6,
+                mass: 0.1,
+                color: Color::rgb(1.0, 0.1, 0.6),
+                etype: EntityType::Mine,
+                lifetime: MINE_LIFETIME,
+                range: f32::MAX,
+            },
+            projectile_count: 1,
+            spread_angle: 0.,
+            energy_cost: 0.,
+            max_ammo: Some(6),
+            rear_mounted: true,
+            sound: Sound::MineDrop,
+            recoil: 0.05,
+        },
+    }
+}
+
+pub const PLAYER_MAX_ENERGY: f32 = 100.;
+pub const PLAYER_ENERGY_RECHARGE_RATE: f32 = 15.;
+
+pub struct PlayerWeapons {
+    pub selected: usize,
+    pub energy: f32,
+    pub ammo: [u32; WEAPON_KINDS.len()],
+    pub cooldown: Cooldown,
+    // When the selected charged weapon started charging
+    pub charge_start_seconds: Option<f64>,
+}
+
+impl Default for PlayerWeapons {
+    fn default() -> Self {
+        let mut ammo = [0; WEAPON_KINDS.len()];
+        for (i, kind) in WEAPON_KINDS.iter().enumerate() {
+            ammo[i] = weapon_parameters(*kind).max_ammo.unwrap_or(0);
+        }
+
+        PlayerWeapons {
+            selected: 0,
+            energy: PLAYER_MAX_ENERGY,
+            ammo,
+            cooldown: Cooldown::default(),
+            charge_start_seconds: None,
+        }
+    }
+}
+
+impl PlayerWeapons {
+    pub fn selected_kind(&self) -> WeaponKind {
+        WEAPON_KINDS[self.selected]
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < WEAPON_KINDS.len() && index != self.selected {
+            self.selected = index;
+            self.charge_start_seconds = None;
+        }
+    }
+
+    fn can_afford(&self, weapon: &WeaponParameters) -> bool {
+        match weapon.max_ammo {
+            Some(_) => self.ammo[self.selected] > 0,
+            None => self.energy >= weapon.energy_cost,
+        }
+    }
+
+    fn spend(&mut self, weapon: &WeaponParameters, charge: f32) {
+        match weapon.max_ammo {
+            Some(_) => self.ammo[self.selected] -= 1,
+            None => self.energy -= weapon.energy_cost * charge,
+        }
+    }
+}
+
+// Handles the trigger of the selected weapon, firing it if the input calls for it.
+// Returns the recoil of the shot, or 0 if nothing was fired
+pub fn fire_weapon(
+    // Systems needed to spawn projectiles
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    bullet_spawner: &mut ResMut<BulletSpawner>,
+
+    // To play sound effects
+    sound_bank: &Res<SoundBank>,
+    audio: &Res<SpatialAudio>,
+
+    time: &Time,
+    mouse_button_input: &Input<MouseButton>,
+    weapons: &mut PlayerWeapons,
+
+    // Info to spawn the projectiles
+    shooter_position: Vec3,
+    shooter_velocity: Vec3,
+    shooter_rotation: Quat,
+    shooter_barrel_length: f32,
+) -> f32 {
+    let weapon = weapon_parameters(weapons.selected_kind());
+
+    let charge = match weapon.trigger {
+        WeaponTrigger::Automatic => {
+            if !mouse_button_input.pressed(MouseButton::Left) {
+                return 0.;
+            }
+            1.
+        }
+        WeaponTrigger::SemiAutomatic => {
+            if !mouse_button_input.just_pressed(MouseButton::Left) {
+                return 0.;
+            }
+            1.
+        }
+        WeaponTrigger::Charged => {
+            if mouse_button_input.just_pressed(MouseButton::Left) && weapons.cooldown.over(time) {
+                weapons.charge_start_seconds = Some(time.seconds_since_startup);
+            }
+            if !mouse_button_input.just_released(MouseButton::Left) {
+                return 0.;
+            }
+            match weapons.charge_start_seconds.take() {
+                Some(charge_start_seconds) => {
+                    let charge_duration = time.seconds_since_startup - charge_start_seconds;
+                    let charge = (charge_duration / RAILGUN_FULL_CHARGE_DURATION).min(1.) as f32;
+                    charge.max(RAILGUN_MIN_CHARGE)
+                }
+                None => return 0.,
+            }
+        }
+    };
+
+    if !weapons.cooldown.over(time) || !weapons.can_afford(&weapon) {
+        return 0.;
+    }
+
+    weapons.spend(&weapon, charge);
+    weapons.cooldown.reset(time, weapon.cooldown);
+
+    play_sound_at(
+        sound_bank,
+        audio,
+        weapon.sound,
+        shooter_position,
+        shooter_velocity,
+    );
+
+    let rotation_mat = Mat3::from_quat(shooter_rotation);
+
+    // shooter is looking down the negative-z axis
+    let shooter_facing = if weapon.rear_mounted {
+        rotation_mat.z_axis
+    } else {
+        -rotation_mat.z_axis
+    };
+
+    let projectile = ProjectileParameters {
+        speed: weapon.projectile.speed * charge,
+        ..weapon.projectile
+    };
+
+    for i in 0..weapon.projectile_count {
+        let spread = if weapon.projectile_count > 1 {
+            let t = i as f32 / (weapon.projectile_count - 1) as f32;
+            (t - 0.5) * weapon.spread_angle
+        } else {
+            0.
+        };
+        let projectile_facing = Quat::from_axis_angle(rotation_mat.y_axis, spread) * shooter_facing;
+
+        if projectile.etype == EntityType::Missile {
+            fire_missile(
+                commands,
+                meshes,
+                materials,
+                bullet_spawner,
+                &projectile,
+                EntityType::Alien,
+                shooter_position,
+                shooter_velocity,
+                projectile_facing,
+                shooter_barrel_length,
+            );
+        } else {
+            fire_projectile(
+                commands,
+                meshes,
+                materials,
+                bullet_spawner,
+                time,
+                &projectile,
+                shooter_position,
+                shooter_velocity,
+                projectile_facing,
+                shooter_barrel_length,
+            );
+        }
+    }
+
+    weapon.recoil * charge
+}
+
+#[derive(Default)]
+pub struct WeaponSelectState {
+    mouse_wheel_event_reader: EventReader<MouseWheel>,
+}
+
+fn weapon_select_update(
+    game_state: Res<GameState>,
+    key_input: Res<Input<KeyCode>>,
+    mut state: Local<WeaponSelectState>,
+    mouse_wheel_events: Res<Events<MouseWheel>>,
+    mut weapons_query: Query<&mut PlayerWeapons>,
+) {
+    if *game_state != GameState::Running {
+        return;
+    }
+
+    let number_keys = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+    ];
+
+    let mut scroll = 0.;
+    for MouseWheel { y, .. } in state.mouse_wheel_event_reader.iter(&mouse_wheel_events) {
+        scroll += *y;
+    }
+
+    for mut weapons in weapons_query.iter_mut() {
+        for (i, key) in number_keys.iter().enumerate() {
+            if key_input.just_pressed(*key) {
+                weapons.select(i);
+            }
+        }
+
+        let weapon_count = WEAPON_KINDS.len();
+        if scroll > 0. {
+            let index = (weapons.selected + weapon_count - 1) % weapon_count;
+            weapons.select(index);
+        } else if scroll < 0. {
+            let index = (weapons.selected + 1) % weapon_count;
+            weapons.select(index);
+        }
+    }
+}
+
+fn weapon_energy_update(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    mut weapons_query: Query<&mut PlayerWeapons>,
+) {
+    if *game_state != GameState::Running {
+        return;
+    }
+
+    for mut weapons in weapons_query.iter_mut() {
+        weapons.energy = (weapons.energy + PLAYER_ENERGY_RECHARGE_RATE * time.delta_seconds)
+            .min(PLAYER_MAX_ENERGY);
+    }
+}
+
+struct WeaponHudMarker;
+
+fn setup_weapon_hud(commands: &mut Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.0),
+                    left: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: String::new(),
+                font,
+                style: TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                    alignment: TextAlignment::default(),
+                },
+            },
+            ..Default::default()
+        })
+        .with(WeaponHudMarker);
+}
+
+fn weapon_hud_text(weapons: &PlayerWeapons, time: &Time) -> String {
+    let mut text = String::new();
+    for (i, kind) in WEAPON_KINDS.iter().enumerate() {
+        let weapon = weapon_parameters(*kind);
+        let label = match weapon.max_ammo {
+            Some(_) => format!("{} {} x{}", i + 1, weapon.name, weapons.ammo[i]),
+            None => format!("{} {}", i + 1, weapon.name),
+        };
+        if i == weapons.selected {
+            text.push_str(&format!("[{}]  ", label));
+        } else {
+            text.push_str(&format!(" {}   ", label));
+        }
+    }
+
+    text.push_str(&format!("\nENERGY {:.0}", weapons.energy));
+
+    if let Some(charge_start_seconds) = weapons.charge_start_seconds {
+        let charge_duration = time.seconds_since_startup - charge_start_seconds;
+        let charge = (charge_duration / RAILGUN_FULL_CHARGE_DURATION).min(1.);
+        text.push_str(&format!("  CHARGE {:.0}%", charge * 100.));
+    }
+
+    text
+}
+
+fn weapon_hud_update(
+    time: Res<Time>,
+    weapons_query: Query<(&PlayerInput, &PlayerWeapons)>,
+    mut hud_query: Query<(&WeaponHudMarker, &mut Text)>,
+) {
+    for (_, weapons) in weapons_query.iter() {
+        for (_, mut text) in hud_query.iter_mut() {
+            text.value = weapon_hud_text(weapons, &time);
+        }
+    }
+}